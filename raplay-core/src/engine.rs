@@ -0,0 +1,894 @@
+use std::{
+    fs::File,
+    io::BufReader,
+    path::Path,
+    sync::{
+        atomic::{AtomicU8, Ordering},
+        mpsc::{channel, Receiver, RecvTimeoutError, Sender},
+        Arc,
+    },
+    thread::{self, JoinHandle},
+    time::Duration,
+};
+
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use rodio::{source::SeekError, Decoder, Sample, Sink, Source};
+
+use crate::{
+    error::{Error, Result},
+    fade::{Crossfade, FadeControl, FadeCurve, Faded},
+    metadata::TrackTags,
+    output::{Output, SINKS},
+};
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum PlayMode {ListOnce, LoopAll, LoopOne, LoopRnd}
+
+impl PlayMode {
+    pub fn next(self) -> Self {
+        match self {
+            PlayMode::ListOnce => PlayMode::LoopAll,
+            PlayMode::LoopAll => PlayMode::LoopOne,
+            PlayMode::LoopOne => PlayMode::LoopRnd,
+            PlayMode::LoopRnd => PlayMode::ListOnce,
+        }
+    }
+    // 对应状态栏的前四个位置：⇒ 顺序一次，↻ 循环，① 单曲，✈ 随机
+    pub fn icons(self) -> &'static str {
+        match self {
+            PlayMode::ListOnce => "⇒ - - -",
+            PlayMode::LoopAll => "- ↻ - -",
+            PlayMode::LoopOne => "- ↻ ① -",
+            PlayMode::LoopRnd => "- ↻ - ✈",
+        }
+    }
+}
+
+// 播放状态：Stopped为已选定当前歌曲但尚未载入（刚加入队列、顺序播放一次播完后）
+#[derive(Clone, Copy, PartialEq)]
+pub enum Status {Stopped, Playing, Paused}
+
+// 界面发给播放线程的命令，歌曲编号从1开始，队列位置从0开始
+pub enum Command {
+    TogglePause,
+    Jump(u16),          // 切换到指定编号，播放中则继续播放，否则载入后暂停
+    PlayId(u16),        // 切换到指定编号并开始播放
+    Next,               // 手动切换下一首，不受单曲循环和顺序播放一次的限制
+    Prev,               // 播放超过3秒时回到本首开头，否则切换上一首
+    Restart,
+    Seek(u64),          // 跳转到指定位置（毫秒），范围由界面按总时长限制
+    SetMode(PlayMode),
+    SetVolume(f32),
+    Replace(Vec<String>, Option<u16>),  // 替换队列并从指定编号开始播放，None为第一首（随机模式下随机选一首）
+    Enqueue(Vec<String>, bool),         // 加入队列末尾，为true时插到当前歌曲之后
+    Remove(usize),
+    Move(usize, bool),  // 把队列中的一项上移（true）或下移一位
+    Clear,
+    Dedupe,
+    Quit,
+}
+
+// 播放线程的当前状态，每次变化以及播放中定时发给界面
+#[derive(Clone)]
+pub struct PlayerState {
+    pub status: Status,
+    pub current: u16,           // 当前歌曲编号，队列为空时为0
+    pub path: String,           // 当前歌曲路径，队列为空时为空
+    pub mode: PlayMode,
+    pub position: Duration,     // 当前播放位置
+}
+
+pub enum Event {
+    State(PlayerState),
+    Queue(Vec<String>),         // 队列内容有变化
+    // 载入了一首歌：编号、路径、总时长（解码器不提供时取自文件头）、声道数和采样率
+    Loaded {id: u16, path: String, duration: Option<Duration>, channels: u16, sample_rate: u32},
+    // 无法播放而被跳过的歌曲：编号、路径和原因
+    Skipped {id: u16, path: String, error: Error},
+    Error(Error),               // 其他操作失败，如当前格式不支持跳转
+    Message(String),            // 给用户的提示信息
+    Finished,                   // 播放结束：顺序播放一次播完了最后一首，或队列中没有能播放的歌曲
+}
+
+pub struct PlayerOptions {
+    pub mode: PlayMode,
+    pub volume: f32,
+    pub seed: Option<u64>,      // 随机播放的种子，None时每次不同
+    pub output: Output,
+    pub crossfade: Option<Crossfade>,   // 歌曲之间的交叉淡化，None时无缝衔接
+}
+
+// 界面持有的播放线程句柄，释放时通知播放线程退出并等待它结束（写完WAV文件）
+pub struct Player {
+    commands: Sender<Command>,
+    pub events: Receiver<Event>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl Player {
+    // 启动播放线程，输出在线程内打开，打开失败（如没有音频设备）时返回错误
+    pub fn spawn(options: PlayerOptions) -> Result<Self> {
+        let (cmd_tx, cmd_rx) = channel();
+        let (event_tx, event_rx) = channel();
+        let (ready_tx, ready_rx) = channel();
+        let thread = thread::Builder::new().name(String::from("player")).spawn(move || {
+            let (sinks, _handle) = match options.output.open(event_tx.clone()) {
+                Ok(pair) => pair,
+                Err(e) => {
+                    let _ = ready_tx.send(Err(e));
+                    return;
+                },
+            };
+            let _ = ready_tx.send(Ok(()));
+            Engine::new(sinks, options, event_tx).run(cmd_rx);
+        }).map_err(|e| Error::Output(e.to_string()))?;
+        ready_rx.recv().map_err(|_| Error::Output(String::from("player thread exited")))??;
+        Ok(Self {commands: cmd_tx, events: event_rx, thread: Some(thread)})
+    }
+    // 播放线程已退出时命令被忽略
+    pub fn send(&self, command: Command) {
+        let _ = self.commands.send(command);
+    }
+}
+
+impl Drop for Player {
+    fn drop(&mut self) {
+        self.send(Command::Quit);
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+// 预先载入的下一首：排在当前歌曲之后，当前歌曲播完时容器直接接着播放，中间没有间隔；
+// 交叉淡化时则放在另一个容器中，当前歌曲开始淡出时同时淡入
+struct Prepared {
+    id: u16,
+    state: Arc<AtomicU8>,
+    control: Arc<FadeControl>,  // 这首歌的淡出控制，切换过去后成为当前歌曲的
+    crossfade: bool,            // 在另一个容器中等待淡入
+    duration: Option<Duration>,
+    tags: TrackTags,
+    channels: u16,
+    sample_rate: u32,
+}
+
+// Prepared的状态：等待中、已开始播放、已取消
+const PENDING: u8 = 0;
+const STARTED: u8 = 1;
+const CANCELLED: u8 = 2;
+
+// 排在当前歌曲之后的音源：输出第一个采样时标记为已开始，之前被取消则直接结束
+struct Queued<S> {
+    inner: S,
+    state: Arc<AtomicU8>,
+    started: bool,
+}
+
+impl<S: Source> Iterator for Queued<S> where S::Item: Sample {
+    type Item = S::Item;
+
+    fn next(&mut self) -> Option<S::Item> {
+        if !self.started {
+            if self.state.compare_exchange(PENDING, STARTED, Ordering::AcqRel, Ordering::Acquire).is_err() {
+                return None;
+            }
+            self.started = true;
+        }
+        self.inner.next()
+    }
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+impl<S: Source> Source for Queued<S> where S::Item: Sample {
+    fn current_frame_len(&self) -> Option<usize> {
+        self.inner.current_frame_len()
+    }
+    fn channels(&self) -> u16 {
+        self.inner.channels()
+    }
+    fn sample_rate(&self) -> u32 {
+        self.inner.sample_rate()
+    }
+    fn total_duration(&self) -> Option<Duration> {
+        self.inner.total_duration()
+    }
+    fn try_seek(&mut self, pos: Duration) -> std::result::Result<(), SeekError> {
+        self.inner.try_seek(pos)
+    }
+}
+
+// 手动切歌时旧歌曲的淡出时长
+const SKIP_FADE: Duration = Duration::from_millis(300);
+
+struct Engine {
+    sinks: [Arc<Sink>; SINKS],
+    active: usize,              // 当前歌曲所在的容器，另一个容器用于交叉淡化
+    queue: Vec<String>,
+    current: u16,
+    status: Status,
+    mode: PlayMode,
+    shuffle_pool: Vec<u16>,     // 随机播放时本轮尚未播放的编号，播完一轮后重新填充
+    rng: StdRng,
+    stale_pos: Option<Duration>,    // 换歌前的播放位置，新歌开始前容器仍会报告这个位置
+    prepared: Option<Prepared>,
+    crossfade: Option<Crossfade>,
+    fade: Arc<FadeControl>,         // 当前歌曲的淡出控制
+    duration: Option<Duration>,     // 当前歌曲的总时长（解码器未提供时取自文件头），用于确定开始淡出的位置
+    tags: TrackTags,                // 当前歌曲的标签，用于判断和下一首是否属于同一张无缝衔接的专辑
+    peeked: Option<(String, TrackTags, Option<Duration>)>,  // 上次预先载入时读取的路径、标签和文件头中的时长
+    prepare_later: bool,            // 另一个容器中的歌曲还在淡出，结束后再预先载入下一首
+    events: Sender<Event>,
+}
+
+impl Engine {
+    fn new(sinks: [Arc<Sink>; SINKS], options: PlayerOptions, events: Sender<Event>) -> Self {
+        for sink in &sinks {
+            sink.set_volume(options.volume);
+        }
+        Self {
+            sinks,
+            active: 0,
+            queue: vec![],
+            current: 0,
+            status: Status::Stopped,
+            mode: options.mode,
+            shuffle_pool: vec![],
+            rng: options.seed.map(StdRng::seed_from_u64).unwrap_or_else(StdRng::from_entropy),
+            stale_pos: None,
+            prepared: None,
+            crossfade: options.crossfade.filter(|crossfade| !crossfade.duration.is_zero()),
+            fade: FadeControl::new(),
+            duration: None,
+            tags: TrackTags::default(),
+            peeked: None,
+            prepare_later: false,
+            events,
+        }
+    }
+    // 处理命令，没有命令时每20毫秒检查一次是否换到了下一首、是否播完，并报告播放位置
+    fn run(mut self, commands: Receiver<Command>) {
+        loop {
+            let command = commands.recv_timeout(Duration::from_millis(20));
+            self.check_prepared();
+            if self.prepare_later && self.other_sink().empty() {
+                self.prepare_next();
+            }
+            let changed = match command {
+                Ok(Command::Quit) | Err(RecvTimeoutError::Disconnected) => break,
+                Ok(command) => {
+                    self.handle(command);
+                    true
+                },
+                Err(RecvTimeoutError::Timeout) => false,
+            };
+            if self.status == Status::Playing && self.sink().empty() {
+                self.advance();
+            }
+            if changed || self.status == Status::Playing {
+                self.send_state();
+            }
+        }
+        for sink in &self.sinks {
+            sink.stop();
+        }
+    }
+    fn sink(&self) -> &Arc<Sink> {
+        &self.sinks[self.active]
+    }
+    fn other_sink(&self) -> &Arc<Sink> {
+        &self.sinks[1 - self.active]
+    }
+    fn curve(&self) -> FadeCurve {
+        self.crossfade.map(|crossfade| crossfade.curve).unwrap_or_default()
+    }
+    fn emit(&self, event: Event) {
+        let _ = self.events.send(event);
+    }
+    fn message(&self, text: String) {
+        self.emit(Event::Message(text));
+    }
+    fn send_state(&mut self) {
+        let position = self.position();
+        self.emit(Event::State(PlayerState {
+            status: self.status,
+            current: self.current,
+            path: self.current_path().to_string(),
+            mode: self.mode,
+            position,
+        }));
+    }
+    fn position(&mut self) -> Duration {
+        let pos = self.sink().get_pos();
+        match self.stale_pos {
+            _ if self.status == Status::Stopped => Duration::ZERO,
+            Some(stale) if stale == pos => Duration::ZERO,
+            _ => {
+                self.stale_pos = None;
+                pos
+            },
+        }
+    }
+    fn send_queue(&self) {
+        self.emit(Event::Queue(self.queue.clone()));
+    }
+    fn current_path(&self) -> &str {
+        self.track_path(self.current)
+    }
+    // 编号对应的路径，编号为0或超出队列时为空
+    fn track_path(&self, id: u16) -> &str {
+        id.checked_sub(1)
+            .and_then(|i| self.queue.get(i as usize))
+            .map_or("", String::as_str)
+    }
+    fn len(&self) -> u16 {
+        self.queue.len() as u16
+    }
+
+    fn handle(&mut self, command: Command) {
+        match command {
+            Command::TogglePause if self.current != 0 => match self.status {
+                Status::Stopped => self.play_id(self.current),
+                // 另一个容器中可能是正在淡出的上一首，一起暂停和继续；等待淡入的下一首由当前歌曲开始
+                Status::Paused => {
+                    self.sink().play();
+                    if !matches!(&self.prepared, Some(prepared) if prepared.crossfade) {
+                        self.other_sink().play();
+                    }
+                    self.status = Status::Playing;
+                },
+                Status::Playing => {
+                    for sink in &self.sinks {
+                        sink.pause();
+                    }
+                    self.status = Status::Paused;
+                },
+            },
+            Command::Jump(id) => self.load(id),
+            Command::PlayId(id) => self.play_id(id),
+            Command::Next if self.current != 0 && !self.queue.is_empty() => {
+                let id = match self.mode {
+                    PlayMode::LoopRnd => self.next_id().unwrap_or(self.current),
+                    _ => self.current % self.len() + 1,
+                };
+                self.load(id);
+            },
+            Command::Prev if self.current != 0 && !self.queue.is_empty() => {
+                let id = if self.position().as_secs() >= 3 {
+                    self.current
+                }
+                else if self.current > 1 {
+                    self.current - 1
+                }
+                else {
+                    self.len()
+                };
+                self.load(id);
+            },
+            Command::Restart if self.current != 0 && !self.queue.is_empty() => self.load(self.current),
+            Command::Seek(msec) => self.seek(msec),
+            Command::SetMode(mode) => {
+                self.mode = mode;
+                // 进入随机模式时，当前这首视为本轮已播放
+                self.reset_shuffle_pool();
+                self.prepare_next();
+            },
+            Command::SetVolume(volume) => {
+                for sink in &self.sinks {
+                    sink.set_volume(volume);
+                }
+            },
+            Command::Replace(list, start) => self.replace(list, start),
+            // 队列变化后下一首可能不同，重新预先载入
+            Command::Enqueue(list, next) => {
+                self.enqueue(list, next);
+                self.prepare_next();
+            },
+            Command::Remove(i) => {
+                self.remove(i);
+                self.prepare_next();
+            },
+            Command::Move(i, up) => {
+                self.move_item(i, up);
+                self.prepare_next();
+            },
+            Command::Clear => self.clear(),
+            Command::Dedupe => {
+                self.dedupe();
+                self.prepare_next();
+            },
+            _ => {},
+        }
+    }
+
+    // 根据播放模式决定下一首的编号，返回None表示列表已播放完毕
+    fn next_id(&mut self) -> Option<u16> {
+        if self.queue.is_empty() {
+            return None;
+        }
+        match self.mode {
+            PlayMode::ListOnce => {
+                if self.current < self.len() {Some(self.current + 1)} else {None}
+            },
+            PlayMode::LoopAll => {
+                if self.current < self.len() {Some(self.current + 1)} else {Some(1)}
+            },
+            PlayMode::LoopOne => Some(self.current),
+            PlayMode::LoopRnd => {
+                if self.shuffle_pool.is_empty() {
+                    self.reset_shuffle_pool();
+                    if self.shuffle_pool.is_empty() {
+                        return Some(self.current);
+                    }
+                }
+                let i = self.rng.gen_range(0..self.shuffle_pool.len());
+                Some(self.shuffle_pool.swap_remove(i))
+            },
+        }
+    }
+    fn reset_shuffle_pool(&mut self) {
+        let curr = self.current;
+        self.shuffle_pool = (1..=self.len()).filter(|&id| id != curr).collect();
+    }
+    // 跳过无法播放的歌曲时的下一首，单曲循环时也换到下一首；返回None表示列表已播放完毕
+    fn skip_id(&mut self) -> Option<u16> {
+        match self.mode {
+            PlayMode::ListOnce if self.current < self.len() => Some(self.current + 1),
+            PlayMode::ListOnce => None,
+            PlayMode::LoopAll | PlayMode::LoopOne => Some(self.current % self.len() + 1),
+            PlayMode::LoopRnd => self.next_id(),
+        }
+    }
+    // 当前歌曲播完时按播放模式切换；顺序播放一次播完最后一首时回到第一首并停止
+    fn advance(&mut self) {
+        match self.next_id() {
+            Some(id) => self.load(id),
+            None => self.finish(),
+        }
+    }
+    // 停止发生在没有命令的定时检查中时不会另外报告状态，这里先发送停止后的状态；队列为空时没有当前歌曲
+    fn finish(&mut self) {
+        self.current = if self.queue.is_empty() {0} else {1};
+        self.status = Status::Stopped;
+        self.send_state();
+        self.emit(Event::Finished);
+    }
+    fn play_id(&mut self, id: u16) {
+        if id == 0 || id > self.len() {
+            return;
+        }
+        self.status = Status::Playing;
+        self.load(id);
+    }
+    fn open(&self, id: u16) -> Result<Decoder<BufReader<File>>> {
+        let path = Path::new(self.track_path(id));
+        let file = File::open(path).map_err(Error::io(path))?;
+        Decoder::new(BufReader::new(file)).map_err(|source| Error::Decode {path: path.to_path_buf(), source})
+    }
+    // 清空容器并载入指定编号的歌曲，播放中则继续播放，否则停在暂停状态
+    // 开启了交叉淡化时，正在播放的歌曲在原容器中短暂淡出，新歌曲在另一个容器中开始
+    // 无法播放的歌曲被跳过，按播放模式换到下一首，整个队列都无法播放时停止
+    fn load(&mut self, id: u16) {
+        if id == 0 || id > self.len() {
+            return;
+        }
+        self.cancel_prepared();
+        if self.crossfade.is_some() && self.status == Status::Playing && !self.sink().is_paused() && !self.sink().empty() {
+            self.fade.fade_out_now(SKIP_FADE);
+            self.active = 1 - self.active;
+        }
+        self.stale_pos = Some(self.sink().get_pos());
+        self.sink().clear();
+        let mut id = id;
+        for _ in 0..self.len() {
+            self.current = id;
+            let source = match self.open(id) {
+                Ok(source) => source,
+                Err(error) => {
+                    self.emit(Event::Skipped {id, path: self.current_path().to_string(), error});
+                    match self.skip_id() {
+                        Some(next) => id = next,
+                        None => return self.finish(),
+                    }
+                    continue;
+                },
+            };
+            let (tags, duration) = TrackTags::read_with_duration(self.current_path());
+            self.tags = tags;
+            self.duration = source.total_duration().or(duration);
+            self.emit(Event::Loaded {
+                id,
+                path: self.current_path().to_string(),
+                duration: self.duration,
+                channels: source.channels(),
+                sample_rate: source.sample_rate(),
+            });
+            self.fade = FadeControl::new();
+            self.sink().append(Faded::new(source, self.fade.clone(), self.curve(), Duration::ZERO));
+            if self.status == Status::Playing {
+                self.sink().play();
+            }
+            else {
+                self.sink().pause();
+                self.status = Status::Paused;
+            }
+            self.prepare_next();
+            return;
+        }
+        self.message(String::from("No playable tracks"));
+        self.finish();
+    }
+    // 预先打开下一首并排在当前歌曲之后，或放到另一个容器中等待交叉淡化；已有预先载入的歌曲时先取消
+    // 打不开的歌曲不预先载入，等当前歌曲播完后按正常流程跳过
+    fn prepare_next(&mut self) {
+        self.cancel_prepared();
+        self.prepare_later = false;
+        if self.status == Status::Stopped || self.sink().empty() {
+            return;
+        }
+        let id = match self.next_id() {
+            Some(id) => id,
+            None => return,
+        };
+        let source = match self.open(id) {
+            Ok(source) => source,
+            Err(_) => {
+                self.return_to_pool(id);
+                return;
+            },
+        };
+        let (tags, duration) = self.peek(id, source.total_duration());
+        let fade = self.crossfade_len(&tags, duration);
+        if fade.is_some() && !self.other_sink().empty() {
+            self.return_to_pool(id);
+            self.prepare_later = true;
+            return;
+        }
+        let state = Arc::new(AtomicU8::new(PENDING));
+        let control = FadeControl::new();
+        self.prepared = Some(Prepared {
+            id,
+            state: state.clone(),
+            control: control.clone(),
+            crossfade: fade.is_some(),
+            duration,
+            tags,
+            channels: source.channels(),
+            sample_rate: source.sample_rate(),
+        });
+        let source = Faded::new(source, control, self.curve(), fade.unwrap_or_default());
+        match (fade, self.duration) {
+            (Some(len), Some(duration)) => {
+                let other = self.other_sink().clone();
+                other.pause();
+                other.append(Queued {inner: source, state, started: false});
+                self.fade.fade_out_at(duration - len, len, other);
+            },
+            _ => self.sink().append(Queued {inner: source, state, started: false}),
+        }
+    }
+    // 当前歌曲与下一首之间交叉淡化的时长，不超过两首歌各自的一半；
+    // 未开启、总时长未知或同属一张无缝衔接的专辑时为None
+    fn crossfade_len(&self, tags: &TrackTags, next: Option<Duration>) -> Option<Duration> {
+        let crossfade = self.crossfade?;
+        let len = crossfade.duration.min(self.duration? / 2).min(next? / 2);
+        if len.is_zero() || self.tags.gapless_with(tags) {
+            return None;
+        }
+        Some(len)
+    }
+    // 下一首的标签和总时长；队列每次变化都会重新预先载入，多半还是同一首，这时不再重新读取文件
+    fn peek(&mut self, id: u16, decoded: Option<Duration>) -> (TrackTags, Option<Duration>) {
+        let path = self.track_path(id);
+        if !matches!(&self.peeked, Some((peeked, ..)) if peeked == path) {
+            let (tags, duration) = TrackTags::read_with_duration(path);
+            self.peeked = Some((path.to_string(), tags, duration));
+        }
+        let (_, tags, duration) = self.peeked.as_ref().unwrap();
+        (tags.clone(), decoded.or(*duration))
+    }
+    // 取消预先载入的下一首；它已经开始播放时不能取消，改为切换过去
+    fn cancel_prepared(&mut self) {
+        if let Some(prepared) = self.prepared.take() {
+            match prepared.state.compare_exchange(PENDING, CANCELLED, Ordering::AcqRel, Ordering::Acquire) {
+                Ok(_) => {
+                    if prepared.crossfade {
+                        self.fade.cancel();
+                        self.other_sink().clear();
+                    }
+                    self.return_to_pool(prepared.id);
+                },
+                Err(_) => self.switch_to(prepared),
+            }
+        }
+    }
+    // 预先载入的下一首已经开始播放时切换过去，并预先载入再下一首
+    fn check_prepared(&mut self) {
+        if !matches!(&self.prepared, Some(prepared) if prepared.state.load(Ordering::Acquire) == STARTED) {
+            return;
+        }
+        if let Some(prepared) = self.prepared.take() {
+            self.switch_to(prepared);
+            self.prepare_next();
+        }
+    }
+    // 交叉淡化时换到下一首所在的容器，原容器中的上一首继续淡出
+    fn switch_to(&mut self, prepared: Prepared) {
+        if prepared.crossfade {
+            self.active = 1 - self.active;
+        }
+        self.fade = prepared.control;
+        self.duration = prepared.duration;
+        self.tags = prepared.tags;
+        self.current = prepared.id;
+        self.stale_pos = None;
+        self.emit(Event::Loaded {
+            id: prepared.id,
+            path: self.current_path().to_string(),
+            duration: prepared.duration,
+            channels: prepared.channels,
+            sample_rate: prepared.sample_rate,
+        });
+    }
+    // 随机播放时预先取出的编号没有播放，放回本轮的候选中
+    fn return_to_pool(&mut self, id: u16) {
+        if self.mode == PlayMode::LoopRnd && id != self.current && id <= self.len() && !self.shuffle_pool.contains(&id) {
+            self.shuffle_pool.push(id);
+        }
+    }
+    fn seek(&mut self, msec: u64) {
+        if self.current == 0 || self.sink().empty() {
+            return;
+        }
+        if let Err(e) = self.sink().try_seek(Duration::from_millis(msec)) {
+            self.emit(Event::Error(Error::Seek(e)));
+        }
+    }
+
+    fn replace(&mut self, list: Vec<String>, start: Option<u16>) {
+        if list.is_empty() {
+            self.message(String::from("No audio files"));
+            return;
+        }
+        // 预先载入的编号属于旧队列，先取消再修改队列，以下各处相同
+        self.cancel_prepared();
+        self.queue = list;
+        // 随机模式下第一首也随机选取，指定种子时顺序可以重现
+        let start = start.unwrap_or_else(|| {
+            if self.mode == PlayMode::LoopRnd {self.rng.gen_range(1..=self.len())} else {1}
+        });
+        self.current = start;
+        self.reset_shuffle_pool();
+        self.send_queue();
+        self.play_id(start);
+    }
+    // 队列原本为空时选定第一首但不载入
+    fn enqueue(&mut self, list: Vec<String>, next: bool) {
+        if list.is_empty() {
+            self.message(String::from("No audio files"));
+            return;
+        }
+        let count = list.len();
+        self.cancel_prepared();
+        if next && self.current != 0 {
+            let at = self.current as usize;
+            self.queue.splice(at..at, list);
+        }
+        else {
+            self.queue.extend(list);
+        }
+        if self.current == 0 {
+            self.current = 1;
+            self.status = Status::Stopped;
+        }
+        self.reset_shuffle_pool();
+        self.send_queue();
+        self.message(format!("{} queued", count));
+    }
+    // 移除的是当前歌曲时改为播放原位置上的下一首
+    fn remove(&mut self, i: usize) {
+        if i >= self.queue.len() {
+            return;
+        }
+        self.cancel_prepared();
+        self.queue.remove(i);
+        let id = i as u16 + 1;
+        if self.queue.is_empty() {
+            return self.clear();
+        }
+        if id < self.current {
+            self.current -= 1;
+        }
+        else if id == self.current {
+            let id = id.min(self.len());
+            if self.status == Status::Stopped {
+                self.current = id;
+            }
+            else {
+                self.load(id);
+            }
+        }
+        self.reset_shuffle_pool();
+        self.send_queue();
+    }
+    fn move_item(&mut self, i: usize, up: bool) {
+        let j = match up {
+            true if i > 0 => i - 1,
+            false if i + 1 < self.queue.len() => i + 1,
+            _ => return,
+        };
+        self.cancel_prepared();
+        self.queue.swap(i, j);
+        let (a, b) = (i as u16 + 1, j as u16 + 1);
+        if self.current == a {
+            self.current = b;
+        }
+        else if self.current == b {
+            self.current = a;
+        }
+        self.reset_shuffle_pool();
+        self.send_queue();
+    }
+    fn clear(&mut self) {
+        self.prepared = None;
+        self.prepare_later = false;
+        for sink in &self.sinks {
+            sink.clear();
+        }
+        self.queue.clear();
+        self.current = 0;
+        self.shuffle_pool.clear();
+        self.status = Status::Stopped;
+        self.send_queue();
+    }
+    // 去除重复项，只保留第一次出现的位置
+    fn dedupe(&mut self) {
+        let before = self.queue.len();
+        self.cancel_prepared();
+        let path = self.current_path().to_string();
+        let mut seen = std::collections::HashSet::new();
+        self.queue.retain(|p| seen.insert(p.clone()));
+        if let Some(i) = self.queue.iter().position(|p| *p == path) {
+            self.current = i as u16 + 1;
+        }
+        self.reset_shuffle_pool();
+        self.send_queue();
+        self.message(format!("{} duplicate(s) removed", before - self.queue.len()));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::*;
+    use crate::testutil::TempDir;
+
+    // 由静音帧（MPEG-1 Layer III、128kbps、44.1kHz、单声道）组成的MP3文件，解码器不提供它的总时长
+    fn silent_mp3(dir: &TempDir, name: &str, frames: usize) -> String {
+        let path = dir.join(name);
+        let mut frame = vec![0u8; 417];
+        frame[..4].copy_from_slice(&[0xff, 0xfb, 0x90, 0xc0]);
+        std::fs::write(&path, frame.repeat(frames)).unwrap();
+        path.to_string_lossy().into_owned()
+    }
+
+    fn spawn(mode: PlayMode) -> Player {
+        spawn_with(mode, Output::Null {realtime: false})
+    }
+
+    fn spawn_with(mode: PlayMode, output: Output) -> Player {
+        spawn_crossfade(mode, output, None)
+    }
+
+    fn spawn_crossfade(mode: PlayMode, output: Output, crossfade: Option<Crossfade>) -> Player {
+        Player::spawn(PlayerOptions {mode, volume: 1.0, seed: Some(1), output, crossfade}).unwrap()
+    }
+
+    // 等待满足条件的事件，超时返回None
+    fn wait_for(player: &Player, mut pred: impl FnMut(&Event) -> bool) -> Option<Event> {
+        let deadline = std::time::Instant::now() + Duration::from_secs(10);
+        while let Some(left) = deadline.checked_duration_since(std::time::Instant::now()) {
+            match player.events.recv_timeout(left) {
+                Ok(event) if pred(&event) => return Some(event),
+                Ok(_) => {},
+                Err(_) => return None,
+            }
+        }
+        None
+    }
+
+    #[test]
+    fn seek_while_paused_without_realtime_output() {
+        let dir = TempDir::new("engine");
+        let player = spawn(PlayMode::ListOnce);
+        player.send(Command::Replace(vec![dir.sine_wav("paused-seek.wav", 44100, 2)], None));
+        assert!(wait_for(&player, |event| matches!(event, Event::Loaded {..})).is_some());
+        player.send(Command::TogglePause);
+        player.send(Command::Seek(1000));
+        player.send(Command::TogglePause);
+        assert!(wait_for(&player, |event| matches!(event, Event::Finished)).is_some());
+    }
+
+    #[test]
+    fn wav_output_records_the_whole_track() {
+        let dir = TempDir::new("engine");
+        let out = dir.join("recorded.wav");
+        let player = spawn_with(PlayMode::ListOnce, Output::Wav {path: out.clone(), realtime: false});
+        player.send(Command::Replace(vec![dir.sine_wav("recorded-source.wav", 44100, 1)], None));
+        assert!(wait_for(&player, |event| matches!(event, Event::Finished)).is_some());
+        drop(player);
+        let reader = hound::WavReader::open(&out).unwrap();
+        assert_eq!(reader.spec().channels, 2);
+        assert_eq!(reader.spec().sample_rate, 44100);
+        // 开头可能有几毫秒容器切换暂停状态时的静音
+        assert!((44100..44100 + 2205).contains(&reader.duration()), "{} frames", reader.duration());
+    }
+
+    #[test]
+    fn list_once_reports_stopped_state_when_finished() {
+        let dir = TempDir::new("engine");
+        let player = spawn(PlayMode::ListOnce);
+        player.send(Command::Replace(vec![dir.sine_wav("once-1.wav", 44100, 1), dir.sine_wav("once-2.wav", 44100, 1)], None));
+        let mut last = None;
+        wait_for(&player, |event| {
+            if let Event::State(state) = event {
+                last = Some((state.status, state.current, state.position));
+            }
+            matches!(event, Event::Finished)
+        }).unwrap();
+        assert!(last == Some((Status::Stopped, 1, Duration::ZERO)));
+    }
+
+    #[test]
+    fn mp3_duration_comes_from_header() {
+        let dir = TempDir::new("engine");
+        let player = spawn(PlayMode::ListOnce);
+        // 每帧1152个采样，77帧约2秒
+        player.send(Command::Replace(vec![silent_mp3(&dir, "header.mp3", 77)], None));
+        let Some(Event::Loaded {duration, ..}) = wait_for(&player, |event| matches!(event, Event::Loaded {..})) else {panic!("not loaded")};
+        let duration = duration.expect("no duration");
+        assert!((1900..2100).contains(&duration.as_millis()), "{:?}", duration);
+    }
+
+    #[test]
+    fn mp3_tracks_crossfade() {
+        let dir = TempDir::new("engine");
+        let out = dir.join("crossfaded.wav");
+        let crossfade = Crossfade {duration: Duration::from_millis(500), curve: FadeCurve::Linear};
+        let player = spawn_crossfade(PlayMode::ListOnce, Output::Wav {path: out.clone(), realtime: false}, Some(crossfade));
+        player.send(Command::Replace(vec![silent_mp3(&dir, "fade-1.mp3", 77), silent_mp3(&dir, "fade-2.mp3", 77)], None));
+        assert!(wait_for(&player, |event| matches!(event, Event::Finished)).is_some());
+        drop(player);
+        // 两首约4秒，重叠0.5秒
+        let frames = hound::WavReader::open(&out).unwrap().duration();
+        assert!((44100 * 33 / 10..44100 * 37 / 10).contains(&frames), "{} frames", frames);
+    }
+
+    #[test]
+    fn commands_on_empty_queue_are_ignored() {
+        let player = spawn(PlayMode::LoopAll);
+        for command in [Command::PlayId(1), Command::Next, Command::Prev, Command::Restart, Command::TogglePause] {
+            player.send(command);
+        }
+        // 播放线程仍在运行，并且没有当前歌曲
+        player.send(Command::Replace(vec![], None));
+        let Some(Event::State(state)) = wait_for(&player, |event| matches!(event, Event::State(_))) else {panic!("player stopped")};
+        assert!(state.status == Status::Stopped && state.current == 0);
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn wav_write_error_is_reported_and_playback_continues() {
+        let dir = TempDir::new("engine");
+        // 写入/dev/full总是失败，和磁盘已满一样
+        let player = spawn_with(PlayMode::ListOnce, Output::Wav {path: PathBuf::from("/dev/full"), realtime: false});
+        player.send(Command::Replace(vec![dir.sine_wav("full-1.wav", 44100, 1), dir.sine_wav("full-2.wav", 44100, 1)], None));
+        assert!(wait_for(&player, |event| matches!(event, Event::Error(Error::Output(_)))).is_some());
+        player.send(Command::Seek(500));
+        player.send(Command::Next);
+        assert!(wait_for(&player, |event| matches!(event, Event::Finished)).is_some());
+    }
+}
@@ -0,0 +1,54 @@
+use std::{
+    fmt, io,
+    path::{Path, PathBuf},
+};
+
+use rodio::{decoder::DecoderError, source::SeekError};
+
+// raplay-core中所有操作的错误
+#[derive(Debug)]
+pub enum Error {
+    Io {path: PathBuf, source: io::Error},          // 读写文件或文件夹失败
+    Decode {path: PathBuf, source: DecoderError},   // 格式不支持或文件已损坏
+    Seek(SeekError),                                // 当前格式不支持跳转等
+    Output(String),                                 // 打不开音频设备或输出文件
+    UnknownPlaylist(PathBuf),                       // 扩展名不是支持的播放列表格式
+    Index {path: PathBuf, source: serde_json::Error},   // 音乐库索引已损坏，无法解析
+    NoDataDir,                                      // 找不到保存音乐库索引的用户数据目录
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+impl Error {
+    // 用于map_err，给io错误附上路径
+    pub(crate) fn io(path: &Path) -> impl FnOnce(io::Error) -> Self + '_ {
+        move |source| Error::Io {path: path.to_path_buf(), source}
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Io {path, source} => write!(f, "{}: {}", path.display(), source),
+            Error::Decode {path, source} => write!(f, "{}: {}", path.display(), source),
+            Error::Seek(SeekError::NotSupported {..}) => write!(f, "Format can't seek"),
+            Error::Seek(e) => write!(f, "Seek failed: {}", e),
+            Error::Output(e) => write!(f, "{}", e),
+            Error::UnknownPlaylist(path) => write!(f, "{}: unknown playlist format", path.display()),
+            Error::Index {path, source} => write!(f, "{}: {}", path.display(), source),
+            Error::NoDataDir => write!(f, "no data directory"),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io {source, ..} => Some(source),
+            Error::Decode {source, ..} => Some(source),
+            Error::Seek(e) => Some(e),
+            Error::Index {source, ..} => Some(source),
+            _ => None,
+        }
+    }
+}
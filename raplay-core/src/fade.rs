@@ -0,0 +1,172 @@
+use std::{
+    f32::consts::FRAC_PI_2,
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    time::Duration,
+};
+
+use rodio::{source::SeekError, Sample, Sink, Source};
+use serde::Deserialize;
+
+// 淡入淡出的曲线：equal_power两首重叠时总响度基本不变，linear在中点会稍微变小
+#[derive(Clone, Copy, PartialEq, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FadeCurve {
+    #[default]
+    EqualPower,
+    Linear,
+}
+
+impl FadeCurve {
+    // t从0到1时的音量
+    fn fade_in(self, t: f32) -> f32 {
+        match self {
+            FadeCurve::EqualPower => (t * FRAC_PI_2).sin(),
+            FadeCurve::Linear => t,
+        }
+    }
+    fn fade_out(self, t: f32) -> f32 {
+        self.fade_in(1.0 - t)
+    }
+}
+
+// 交叉淡化的设置
+#[derive(Clone, Copy)]
+pub struct Crossfade {
+    pub duration: Duration,
+    pub curve: FadeCurve,
+}
+
+// 播放线程对正在播放的歌曲的淡出控制
+pub(crate) struct FadeControl {
+    out_start: AtomicU64,       // 开始淡出的位置（毫秒），u64::MAX为不淡出
+    out_len: AtomicU64,         // 淡出时长（毫秒），淡出结束时歌曲随之结束
+    out_now: AtomicBool,        // 从当前位置立即开始淡出
+    has_next: AtomicBool,
+    next: Mutex<Option<Arc<Sink>>>,     // 开始淡出时同时开始播放的容器，其中是淡入的下一首
+}
+
+impl FadeControl {
+    pub(crate) fn new() -> Arc<Self> {
+        Arc::new(Self {
+            out_start: AtomicU64::new(u64::MAX),
+            out_len: AtomicU64::new(0),
+            out_now: AtomicBool::new(false),
+            has_next: AtomicBool::new(false),
+            next: Mutex::new(None),
+        })
+    }
+    // 播放到start时开始用len淡出，同时开始播放next
+    pub(crate) fn fade_out_at(&self, start: Duration, len: Duration, next: Arc<Sink>) {
+        *self.next.lock().unwrap() = Some(next);
+        self.has_next.store(true, Ordering::Release);
+        self.out_len.store(len.as_millis() as u64, Ordering::Release);
+        self.out_start.store(start.as_millis() as u64, Ordering::Release);
+    }
+    // 立即淡出，用于手动切歌
+    pub(crate) fn fade_out_now(&self, len: Duration) {
+        self.out_len.store(len.as_millis() as u64, Ordering::Release);
+        self.out_now.store(true, Ordering::Release);
+    }
+    // 取消尚未开始的淡出，返回原本要同时开始的容器
+    pub(crate) fn cancel(&self) -> Option<Arc<Sink>> {
+        self.out_start.store(u64::MAX, Ordering::Release);
+        self.has_next.store(false, Ordering::Release);
+        self.next.lock().unwrap().take()
+    }
+}
+
+// 按播放位置调整音量的音源：开头淡入，FadeControl指定的位置开始淡出
+pub(crate) struct Faded<S> {
+    inner: S,
+    control: Arc<FadeControl>,
+    curve: FadeCurve,
+    fade_in: f64,           // 淡入时长（毫秒），0为不淡入
+    frames: u64,            // 已输出的帧数，用于计算播放位置
+    sample: u16,            // 当前帧中已输出的采样数
+    gain: f32,
+}
+
+impl<S: Source> Faded<S> where S::Item: Sample {
+    pub(crate) fn new(inner: S, control: Arc<FadeControl>, curve: FadeCurve, fade_in: Duration) -> Self {
+        Self {
+            inner,
+            control,
+            curve,
+            fade_in: fade_in.as_secs_f64() * 1000.0,
+            frames: 0,
+            sample: 0,
+            gain: 1.0,
+        }
+    }
+    // 每帧开始时按位置计算音量，淡出结束时返回false
+    fn update_gain(&mut self) -> bool {
+        let pos = self.frames as f64 * 1000.0 / self.inner.sample_rate().max(1) as f64;
+        let mut gain = 1.0;
+        if pos < self.fade_in {
+            gain *= self.curve.fade_in((pos / self.fade_in) as f32);
+        }
+        if self.control.out_now.swap(false, Ordering::AcqRel) {
+            self.control.out_start.store(pos as u64, Ordering::Release);
+        }
+        let start = self.control.out_start.load(Ordering::Acquire);
+        if start != u64::MAX && pos >= start as f64 {
+            if self.control.has_next.swap(false, Ordering::AcqRel) {
+                if let Some(next) = self.control.next.lock().unwrap().take() {
+                    next.play();
+                }
+            }
+            let len = self.control.out_len.load(Ordering::Acquire).max(1) as f64;
+            let t = (pos - start as f64) / len;
+            if t >= 1.0 {
+                return false;
+            }
+            gain *= self.curve.fade_out(t as f32);
+        }
+        self.gain = gain;
+        true
+    }
+}
+
+impl<S: Source> Iterator for Faded<S> where S::Item: Sample {
+    type Item = S::Item;
+
+    fn next(&mut self) -> Option<S::Item> {
+        if self.sample == 0 && !self.update_gain() {
+            return None;
+        }
+        let value = self.inner.next()?;
+        self.sample += 1;
+        if self.sample >= self.inner.channels().max(1) {
+            self.sample = 0;
+            self.frames += 1;
+        }
+        Some(if self.gain == 1.0 {value} else {value.amplify(self.gain)})
+    }
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+impl<S: Source> Source for Faded<S> where S::Item: Sample {
+    fn current_frame_len(&self) -> Option<usize> {
+        self.inner.current_frame_len()
+    }
+    fn channels(&self) -> u16 {
+        self.inner.channels()
+    }
+    fn sample_rate(&self) -> u32 {
+        self.inner.sample_rate()
+    }
+    fn total_duration(&self) -> Option<Duration> {
+        self.inner.total_duration()
+    }
+    fn try_seek(&mut self, pos: Duration) -> Result<(), SeekError> {
+        self.inner.try_seek(pos)?;
+        self.frames = (pos.as_secs_f64() * self.inner.sample_rate() as f64) as u64;
+        self.sample = 0;
+        Ok(())
+    }
+}
@@ -0,0 +1,20 @@
+// raplay的核心部分，不依赖终端界面，可以嵌入其他工具中使用
+//
+// engine      播放线程：通过Player发送命令控制播放，从events接收状态
+// library     音乐库索引和增量扫描，以及文件夹内音频文件的收集
+// metadata    标签、格式和时长等歌曲信息的读取，以及按路径的缓存和后台计算时长
+// playlist    M3U、PLS、XSPF播放列表的读写
+// fade        歌曲之间的交叉淡化和切歌时的淡出
+// output      声卡、丢弃采样或写入WAV文件的输出，用于没有声卡的机器和测试
+// error       以上各模块共用的错误类型
+// testutil    测试共用的临时文件夹和音频文件
+pub mod engine;
+pub mod error;
+pub mod fade;
+pub mod library;
+pub mod metadata;
+pub mod output;
+pub mod playlist;
+
+#[cfg(test)]
+mod testutil;
@@ -0,0 +1,294 @@
+use std::{
+    collections::{BTreeMap, BTreeSet, HashSet},
+    fs::{self, read_dir, File},
+    io::{self, BufReader, BufWriter},
+    path::{Path, PathBuf},
+    time::UNIX_EPOCH,
+};
+
+use lofty::prelude::AudioFile;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    error::{Error, Result},
+    metadata::TrackTags,
+};
+
+pub fn is_audio_file(name: &str) -> bool {
+    match Path::new(name).extension().and_then(|ext| ext.to_str()) {
+        Some(ext) => matches!(ext.to_ascii_lowercase().as_str(), "mp3" | "flac" | "ogg" | "wav"),
+        None => false,
+    }
+}
+
+// 递归收集文件夹内的所有音频文件，按路径排序；返回因路径不是有效的UTF-8而跳过的文件数
+pub fn collect_audio_files(path: &Path, out: &mut Vec<String>) -> Result<usize> {
+    let mut entries = read_dir(path).map_err(Error::io(path))?.filter_map(|item| item.ok()).collect::<Vec<_>>();
+    entries.sort_by_key(|item| item.file_name());
+    let mut skipped = 0;
+    for item in entries {
+        let file_type = item.file_type().map_err(Error::io(&item.path()))?;
+        if file_type.is_dir() {
+            skipped += collect_audio_files(&item.path(), out)?;
+        }
+        else if file_type.is_file() && is_audio_file(&item.file_name().to_string_lossy()) {
+            match item.path().to_str() {
+                Some(p) => out.push(p.to_string()),
+                None => skipped += 1,
+            }
+        }
+    }
+    Ok(skipped)
+}
+
+// 音乐库中的一首歌，mtime用于增量扫描时判断文件是否有改动
+#[derive(Clone, Serialize, Deserialize)]
+pub struct LibraryTrack {
+    pub mtime: u64,                 // 文件修改时间（Unix时间戳，秒）
+    pub duration_ms: Option<u64>,   // 文件头记录的总时长（毫秒），没有记录时为None
+    pub tags: TrackTags,
+}
+
+impl LibraryTrack {
+    fn read(path: &str, mtime: u64) -> Self {
+        match lofty::read_from_path(path) {
+            Ok(file) => Self {
+                mtime,
+                duration_ms: Some(file.properties().duration().as_millis() as u64).filter(|&ms| ms > 0),
+                tags: TrackTags::from_file(&file),
+            },
+            Err(_) => Self {
+                mtime,
+                duration_ms: None,
+                tags: TrackTags::default(),
+            },
+        }
+    }
+}
+
+// 增量扫描的结果统计
+#[derive(Default)]
+pub struct ScanStats {
+    pub added: usize,
+    pub updated: usize,
+    pub removed: usize,
+    pub skipped: usize,     // 路径不是有效的UTF-8而无法加入索引的歌曲
+}
+
+// 持久化的音乐库索引，保存在用户数据目录下的raplay/library.json
+#[derive(Clone, Default, Serialize, Deserialize)]
+pub struct Library {
+    pub roots: Vec<PathBuf>,                        // 已添加的音乐库根文件夹
+    pub tracks: BTreeMap<String, LibraryTrack>,     // 以完整路径为键的所有歌曲，按路径排序
+}
+
+impl Library {
+    pub fn db_path() -> Option<PathBuf> {
+        dirs::data_dir().map(|dir| dir.join("raplay").join("library.json"))
+    }
+    pub fn load() -> Result<Self> {
+        match Self::db_path() {
+            Some(path) => Self::load_from(&path),
+            None => Ok(Self::default()),
+        }
+    }
+    // 读取音乐库索引，文件不存在时返回空库；无法解析时返回错误，
+    // 并把损坏的索引改名为.bak保留下来，避免之后保存时被覆盖
+    pub fn load_from(path: &Path) -> Result<Self> {
+        let file = match File::open(path) {
+            Ok(file) => file,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Self::default()),
+            Err(e) => return Err(Error::io(path)(e)),
+        };
+        serde_json::from_reader(BufReader::new(file)).map_err(|source| {
+            let _ = fs::rename(path, path.with_extension("json.bak"));
+            Error::Index {path: path.to_path_buf(), source}
+        })
+    }
+    pub fn save(&self) -> Result<()> {
+        self.save_to(&Self::db_path().ok_or(Error::NoDataDir)?)
+    }
+    // 先写入临时文件再改名替换，写到一半失败（磁盘已满、被终止）时原来的索引不受影响
+    pub fn save_to(&self, path: &Path) -> Result<()> {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir).map_err(Error::io(dir))?;
+        }
+        let tmp = path.with_extension("json.tmp");
+        File::create(&tmp)
+            .and_then(|file| {
+                let mut out = BufWriter::new(file);
+                serde_json::to_writer(&mut out, self)?;
+                out.into_inner().map_err(io::IntoInnerError::into_error)?.sync_all()
+            })
+            .and_then(|_| fs::rename(&tmp, path))
+            .map_err(Error::io(path))
+    }
+    // 添加根文件夹，已在库中（含被已有根文件夹包含）时返回false
+    pub fn add_root(&mut self, path: &Path) -> Result<bool> {
+        let path = fs::canonicalize(path).map_err(Error::io(path))?;
+        if self.contains(&path) {
+            return Ok(false);
+        }
+        self.roots.retain(|root| !root.starts_with(&path));
+        self.roots.push(path);
+        Ok(true)
+    }
+    pub fn contains(&self, dir: &Path) -> bool {
+        self.roots.iter().any(|root| dir.starts_with(root))
+    }
+    // 遍历所有根文件夹，只重新读取新增或修改时间有变化的文件，并移除已不存在的文件
+    pub fn rescan(&mut self) -> ScanStats {
+        let mut stats = ScanStats::default();
+        let mut seen = HashSet::new();
+        for root in self.roots.clone() {
+            self.scan_dir(&root, &mut seen, &mut stats);
+        }
+        let before = self.tracks.len();
+        self.tracks.retain(|path, _| seen.contains(path));
+        stats.removed = before - self.tracks.len();
+        stats
+    }
+    fn scan_dir(&mut self, dir: &Path, seen: &mut HashSet<String>, stats: &mut ScanStats) {
+        let entries = match read_dir(dir) {
+            Ok(entries) => entries,
+            Err(_) => return,
+        };
+        for item in entries.flatten() {
+            let file_type = match item.file_type() {
+                Ok(file_type) => file_type,
+                Err(_) => continue,
+            };
+            let path = item.path();
+            if file_type.is_dir() {
+                self.scan_dir(&path, seen, stats);
+                continue;
+            }
+            if !file_type.is_file() || !is_audio_file(&path.to_string_lossy()) {
+                continue;
+            }
+            let key = match path.to_str() {
+                Some(key) => key.to_string(),
+                None => {
+                    stats.skipped += 1;
+                    continue;
+                },
+            };
+            let mtime = item.metadata().ok()
+                .and_then(|meta| meta.modified().ok())
+                .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+                .map(|time| time.as_secs())
+                .unwrap_or(0);
+            seen.insert(key.clone());
+            match self.tracks.get(&key) {
+                Some(track) if track.mtime == mtime => continue,
+                Some(_) => stats.updated += 1,
+                None => stats.added += 1,
+            }
+            let track = LibraryTrack::read(&key, mtime);
+            self.tracks.insert(key, track);
+        }
+    }
+    // 列出库中某文件夹下的子文件夹名和歌曲文件名
+    pub fn list_dir(&self, dir: &Path) -> (Vec<String>, Vec<String>) {
+        let mut dirs = BTreeSet::new();
+        let mut files = vec![];
+        for path in self.tracks.keys() {
+            let rest = match Path::new(path).strip_prefix(dir) {
+                Ok(rest) => rest,
+                Err(_) => continue,
+            };
+            let mut components = rest.components();
+            match (components.next(), components.next()) {
+                (Some(first), Some(_)) => {
+                    dirs.insert(first.as_os_str().to_string_lossy().into_owned());
+                },
+                (Some(first), None) => files.push(first.as_os_str().to_string_lossy().into_owned()),
+                _ => {},
+            }
+        }
+        (dirs.into_iter().collect(), files)
+    }
+    // 库中某文件夹下（含子文件夹）的所有歌曲路径
+    pub fn tracks_under(&self, dir: &Path) -> Vec<String> {
+        self.tracks.keys()
+            .filter(|path| Path::new(path).starts_with(dir))
+            .cloned()
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil::TempDir;
+
+    #[test]
+    fn missing_index_loads_empty() {
+        let temp = TempDir::new("library");
+        let dir = temp.path();
+        let library = Library::load_from(&dir.join("library.json")).unwrap();
+        assert!(library.roots.is_empty() && library.tracks.is_empty());
+    }
+
+    #[test]
+    fn corrupt_index_is_reported_and_kept() {
+        let temp = TempDir::new("library");
+        let dir = temp.path();
+        let path = dir.join("library.json");
+        fs::write(&path, "{\"roots\": [\"/music\"], \"tra").unwrap();
+        assert!(matches!(Library::load_from(&path), Err(Error::Index {..})));
+        assert!(!path.exists());
+        assert_eq!(fs::read_to_string(dir.join("library.json.bak")).unwrap(), "{\"roots\": [\"/music\"], \"tra");
+    }
+
+    #[test]
+    fn save_replaces_index_without_leftovers() {
+        let temp = TempDir::new("library");
+        let dir = temp.path();
+        let path = dir.join("library.json");
+        fs::write(&path, "old").unwrap();
+        let library = Library {roots: vec![PathBuf::from("/music")], ..Library::default()};
+        library.save_to(&path).unwrap();
+        assert_eq!(Library::load_from(&path).unwrap().roots, vec![PathBuf::from("/music")]);
+        assert!(!dir.join("library.json.tmp").exists());
+    }
+
+    #[test]
+    fn rescan_is_incremental() {
+        let temp = TempDir::new("library");
+        let dir = temp.path();
+        fs::create_dir(dir.join("album")).unwrap();
+        fs::write(dir.join("album").join("a.mp3"), b"").unwrap();
+        fs::write(dir.join("b.flac"), b"").unwrap();
+        fs::write(dir.join("cover.jpg"), b"").unwrap();
+        let mut library = Library::default();
+        assert!(library.add_root(dir).unwrap());
+        assert!(!library.add_root(&dir.join("album")).unwrap());
+        let stats = library.rescan();
+        assert_eq!((stats.added, stats.updated, stats.removed), (2, 0, 0));
+        let stats = library.rescan();
+        assert_eq!((stats.added, stats.updated, stats.removed), (0, 0, 0));
+        fs::remove_file(dir.join("b.flac")).unwrap();
+        let stats = library.rescan();
+        assert_eq!((stats.added, stats.updated, stats.removed), (0, 0, 1));
+        let root = fs::canonicalize(dir).unwrap();
+        assert_eq!(library.list_dir(&root), (vec![String::from("album")], vec![]));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn non_utf8_names_are_counted() {
+        use std::{ffi::OsStr, os::unix::ffi::OsStrExt};
+        let temp = TempDir::new("library");
+        let dir = fs::canonicalize(temp.path()).unwrap();
+        fs::write(dir.join(OsStr::from_bytes(b"\xff.mp3")), b"").unwrap();
+        fs::write(dir.join("ok.mp3"), b"").unwrap();
+        let mut list = vec![];
+        assert_eq!(collect_audio_files(&dir, &mut list).unwrap(), 1);
+        assert_eq!(list.len(), 1);
+        let mut library = Library::default();
+        library.add_root(&dir).unwrap();
+        let stats = library.rescan();
+        assert_eq!((stats.added, stats.skipped), (1, 1));
+    }
+}
@@ -0,0 +1,259 @@
+use std::{
+    collections::HashMap,
+    fs::{self, File},
+    io::BufReader,
+    path::Path,
+    sync::mpsc::{channel, Receiver, Sender},
+    thread,
+    time::Duration,
+};
+
+use lofty::{
+    prelude::{Accessor, AudioFile, ItemKey, TaggedFileExt},
+    probe::Probe,
+    config::ParseOptions,
+    file::TaggedFile,
+    properties::FileProperties,
+};
+use rodio::{Decoder, Source};
+use serde::{Deserialize, Serialize};
+
+// 从ID3v2、Vorbis comment（含FLAC）、MP4等内嵌标签读取的歌曲信息，没有的项为None
+#[derive(Clone, Default, Serialize, Deserialize)]
+pub struct TrackTags {
+    pub title: Option<String>,
+    pub artist: Option<String>,
+    pub album: Option<String>,
+    pub track: Option<u32>,
+    pub disc: Option<u32>,
+    pub year: Option<u32>,
+    pub genre: Option<String>,
+    #[serde(default)]
+    pub gapless: bool,      // iTunes的ITUNPGAP标记：属于无缝衔接的专辑，与同专辑的前后曲之间不做交叉淡化
+}
+
+impl TrackTags {
+    // 读取文件的标签，优先使用该格式的主标签，读取失败或没有标签时返回全空的结果
+    pub fn read(path: &str) -> Self {
+        match Probe::open(path)
+            .and_then(|probe| probe.options(ParseOptions::new().read_properties(false)).read())
+        {
+            Ok(file) => Self::from_file(&file),
+            Err(_) => Self::default(),
+        }
+    }
+    // 同时读取标签和文件头中记录的总时长，用于MP3、OGG等解码器不提供总时长的格式
+    pub fn read_with_duration(path: &str) -> (Self, Option<Duration>) {
+        match Probe::open(path).and_then(|probe| probe.read()) {
+            Ok(file) => (Self::from_file(&file), Some(file.properties().duration()).filter(|dur| !dur.is_zero())),
+            Err(_) => (Self::default(), None),
+        }
+    }
+    pub fn from_file(file: &TaggedFile) -> Self {
+        let tag = match file.primary_tag().or(file.first_tag()) {
+            Some(tag) => tag,
+            None => return Self::default(),
+        };
+        let text = |s: Option<std::borrow::Cow<'_, str>>| {
+            s.map(|s| s.trim().to_string()).filter(|s| !s.is_empty())
+        };
+        Self {
+            title: text(tag.title()),
+            artist: text(tag.artist()),
+            album: text(tag.album()),
+            track: tag.track(),
+            disc: tag.disk(),
+            year: tag.year(),
+            genre: text(tag.genre()),
+            gapless: tag.get_string(&ItemKey::Unknown(String::from("ITUNPGAP"))).map(str::trim) == Some("1"),
+        }
+    }
+    // 显示用的歌名：有标题时为"艺术家 - 标题"，否则为文件名
+    pub fn display_name(&self, path: &str) -> String {
+        match (&self.title, &self.artist) {
+            (Some(title), Some(artist)) => format!("{} - {}", artist, title),
+            (Some(title), None) => title.clone(),
+            _ => Path::new(path)
+                .file_name()
+                .map(|name| name.to_string_lossy().into_owned())
+                .unwrap_or_default(),
+        }
+    }
+    // 两首歌属于同一张无缝衔接的专辑
+    pub fn gapless_with(&self, other: &TrackTags) -> bool {
+        self.gapless && other.gapless && self.album.is_some() && self.album == other.album
+    }
+    // 专辑、年份、碟号、音轨号、流派，用于正在播放区域的标题栏
+    pub fn album_desc(&self) -> String {
+        let mut parts = vec![];
+        if let Some(album) = &self.album {
+            match self.year {
+                Some(year) => parts.push(format!("{} ({})", album, year)),
+                None => parts.push(album.clone()),
+            }
+        }
+        else if let Some(year) = self.year {
+            parts.push(year.to_string());
+        }
+        if let Some(disc) = self.disc {
+            parts.push(format!("Disc {}", disc));
+        }
+        if let Some(track) = self.track {
+            parts.push(format!("#{}", track));
+        }
+        if let Some(genre) = &self.genre {
+            parts.push(genre.clone());
+        }
+        parts.join(" · ")
+    }
+}
+
+// 读取文件头中的格式信息（码率、位深等），不读取标签
+pub fn read_properties(path: &str) -> Option<FileProperties> {
+    Probe::open(path)
+        .and_then(|probe| probe.options(ParseOptions::new().read_tags(false)).read())
+        .map(|file| file.properties().clone())
+        .ok()
+}
+
+// 用文件大小和总时长估算平均码率（kbps）
+pub fn estimate_bitrate(path: &str, dur: Duration) -> Option<u32> {
+    let size = fs::metadata(path).ok()?.len();
+    let msec = dur.as_millis() as u64;
+    if msec == 0 {
+        return None;
+    }
+    Some((size * 8 / msec) as u32)
+}
+
+// 对容器未提供总时长的格式，完整解码一遍并按采样数计算时长
+pub fn scan_duration(path: &str) -> Option<Duration> {
+    let source = Decoder::new(BufReader::new(File::open(path).ok()?)).ok()?;
+    let channels = source.channels() as u64;
+    let sample_rate = source.sample_rate() as u64;
+    if channels == 0 || sample_rate == 0 {
+        return None;
+    }
+    let frames = source.count() as u64 / channels;
+    Some(Duration::from_millis(frames * 1000 / sample_rate))
+}
+
+// 每首歌载入时读取一次的格式信息和总时长
+#[derive(Clone)]
+pub struct TrackInfo {
+    pub duration: Option<Duration>, // 总时长，都没有提供时由后台线程完整解码计算，计算完成前为None
+    pub channels: u16,              // 声道数
+    pub sample_rate: u32,           // 采样率
+    pub bit_depth: Option<u8>,      // 位深，有损格式为None
+    pub bitrate: Option<u32>,       // 平均码率（kbps），容器未提供时由文件大小和总时长估算
+}
+
+impl TrackInfo {
+    // 形如"44.1kHz 16bit 2ch"的格式信息
+    pub fn format_desc(&self) -> String {
+        let rate = format!("{:.1}", self.sample_rate as f32 / 1000.0);
+        let rate = format!("{}kHz", rate.trim_end_matches(".0"));
+        match self.bit_depth {
+            Some(bits) => format!("{} {}bit {}ch", rate, bits, self.channels),
+            None => format!("{} {}ch", rate, self.channels),
+        }
+    }
+}
+
+// 后台计算时长的结果：路径和计算出的总时长
+type ScanResult = (String, Option<Duration>);
+
+// 按路径缓存的歌曲信息，避免每次刷新都重新读取文件；MP3、OGG等解码器不提供总时长的格式
+// 依次使用文件头中的时长、调用方提供的时长（如播放列表中记录的），都没有时交给后台线程完整解码计算
+pub struct TrackInfoCache {
+    tracks: HashMap<String, TrackInfo>,
+    scans: (Sender<ScanResult>, Receiver<ScanResult>),
+}
+
+impl Default for TrackInfoCache {
+    fn default() -> Self {
+        Self {tracks: HashMap::new(), scans: channel()}
+    }
+}
+
+impl TrackInfoCache {
+    pub fn get(&self, path: &str) -> Option<&TrackInfo> {
+        self.tracks.get(path)
+    }
+    pub fn duration(&self, path: &str) -> Option<Duration> {
+        self.get(path).and_then(|info| info.duration)
+    }
+    // 载入歌曲时记录其信息，已缓存的路径直接跳过；duration为解码器提供的总时长，fallback在文件头也没有时使用
+    pub fn insert(&mut self, path: &str, duration: Option<Duration>, fallback: Option<Duration>, channels: u16, sample_rate: u32) {
+        if self.tracks.contains_key(path) {
+            return;
+        }
+        let properties = read_properties(path);
+        let duration = duration
+            .or_else(|| properties.as_ref().map(FileProperties::duration).filter(|dur| !dur.is_zero()))
+            .or(fallback);
+        let bitrate = properties.as_ref()
+            .and_then(|p| p.audio_bitrate().or(p.overall_bitrate()))
+            .filter(|&kbps| kbps > 0)
+            .or_else(|| duration.and_then(|dur| estimate_bitrate(path, dur)));
+        if duration.is_none() {
+            let path = path.to_string();
+            let tx = self.scans.0.clone();
+            thread::spawn(move || {
+                let dur = scan_duration(&path);
+                let _ = tx.send((path, dur));
+            });
+        }
+        self.tracks.insert(path.to_string(), TrackInfo {
+            duration,
+            channels,
+            sample_rate,
+            bit_depth: properties.and_then(|p| p.bit_depth()),
+            bitrate,
+        });
+    }
+    // 接收后台线程计算出的总时长，需要定期调用
+    pub fn update(&mut self) {
+        while let Ok((path, dur)) = self.scans.1.try_recv() {
+            if let Some(info) = self.tracks.get_mut(&path) {
+                info.duration = dur;
+                if info.bitrate.is_none() {
+                    info.bitrate = dur.and_then(|dur| estimate_bitrate(&path, dur));
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil::TempDir;
+
+    fn info(sample_rate: u32, bit_depth: Option<u8>) -> TrackInfo {
+        TrackInfo {duration: None, channels: 2, sample_rate, bit_depth, bitrate: None}
+    }
+
+    #[test]
+    fn format_description() {
+        assert_eq!(info(44100, Some(16)).format_desc(), "44.1kHz 16bit 2ch");
+        assert_eq!(info(48000, None).format_desc(), "48kHz 2ch");
+    }
+
+    #[test]
+    fn cache_falls_back_to_header_then_hint() {
+        let dir = TempDir::new("metadata");
+        let wav = dir.sine_wav("header.wav", 8000, 2);
+        let missing = dir.join("missing.mp3").to_string_lossy().into_owned();
+
+        let mut cache = TrackInfoCache::default();
+        cache.insert(&wav, None, Some(Duration::from_secs(9)), 1, 8000);
+        cache.insert(&missing, None, Some(Duration::from_secs(9)), 2, 44100);
+        assert_eq!(cache.duration(&wav), Some(Duration::from_secs(2)));
+        assert_eq!(cache.get(&wav).and_then(|info| info.bit_depth), Some(16));
+        assert_eq!(cache.duration(&missing), Some(Duration::from_secs(9)));
+        // 已缓存的路径不会被覆盖
+        cache.insert(&wav, Some(Duration::from_secs(1)), None, 1, 8000);
+        assert_eq!(cache.duration(&wav), Some(Duration::from_secs(2)));
+    }
+}
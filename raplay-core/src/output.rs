@@ -0,0 +1,149 @@
+use std::{
+    fs::File,
+    io::BufWriter,
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc::Sender,
+        Arc,
+    },
+    thread::{self, JoinHandle},
+    time::{Duration, Instant},
+};
+
+use hound::{SampleFormat, WavSpec, WavWriter};
+use rodio::{dynamic_mixer::{self, DynamicMixer}, OutputStream, Sink};
+
+use crate::{
+    engine::Event,
+    error::{Error, Result},
+};
+
+// 播放的输出位置；没有声卡的机器（CI容器、服务器）可以用Null或Wav运行整个播放器
+// realtime为true时按真实时间消耗采样，暂停时消耗的是静音，和声卡一样；
+// 为false时尽快消耗，暂停或没有歌曲时停下等待，几秒钟就能“播”完一整张专辑
+#[derive(Clone)]
+pub enum Output {
+    Device,                                 // 系统默认的音频设备
+    Null {realtime: bool},                  // 丢弃所有采样
+    Wav {path: PathBuf, realtime: bool},    // 写入WAV文件（44.1kHz、16bit、双声道），最长约6.8小时
+}
+
+// 播放线程使用的容器数：交叉淡化时两首歌分别在两个容器中同时播放
+pub(crate) const SINKS: usize = 2;
+
+// 不使用声卡时统一转换成的格式
+const SAMPLE_RATE: u32 = 44100;
+const CHANNELS: u16 = 2;
+
+// WAV文件头中的长度是32位的，数据部分写到约4GB（44.1kHz双声道约6.8小时）后不再写入，播放照常继续
+const WAV_MAX_SAMPLES: u32 = (u32::MAX - 1024) / 2 / CHANNELS as u32 * CHANNELS as u32;
+
+// 保持输出可用的句柄：声卡的OutputStream，或消耗采样的后台线程；释放时线程结束并写好WAV文件头
+pub(crate) enum OutputHandle {
+    Device {_stream: OutputStream},
+    Thread {stop: Arc<AtomicBool>, thread: Option<JoinHandle<()>>},
+}
+
+impl Drop for OutputHandle {
+    fn drop(&mut self) {
+        if let OutputHandle::Thread {stop, thread} = self {
+            stop.store(true, Ordering::Relaxed);
+            if let Some(thread) = thread.take() {
+                let _ = thread.join();
+            }
+        }
+    }
+}
+
+impl Output {
+    // 打开输出并创建混合到同一输出的容器；OutputStream不能跨线程移动，必须在使用Sink的线程中调用
+    // 之后写入文件失败时通过events报告
+    pub(crate) fn open(&self, events: Sender<Event>) -> Result<([Arc<Sink>; SINKS], OutputHandle)> {
+        let (writer, realtime) = match self {
+            Output::Device => {
+                let (stream, handle) = OutputStream::try_default().map_err(|e| Error::Output(e.to_string()))?;
+                let sink = || Sink::try_new(&handle).map(Arc::new).map_err(|e| Error::Output(e.to_string()));
+                return Ok(([sink()?, sink()?], OutputHandle::Device {_stream: stream}));
+            },
+            Output::Null {realtime} => (None, *realtime),
+            Output::Wav {path, realtime} => {
+                let spec = WavSpec {channels: CHANNELS, sample_rate: SAMPLE_RATE, bits_per_sample: 16, sample_format: SampleFormat::Int};
+                let file = File::create(path).map_err(Error::io(path))?;
+                let writer = WavWriter::new(BufWriter::new(file), spec).map_err(|e| Error::Output(e.to_string()))?;
+                (Some(writer), *realtime)
+            },
+        };
+        let (mixer, output) = dynamic_mixer::mixer(CHANNELS, SAMPLE_RATE);
+        let sinks = [(); SINKS].map(|_| {
+            let (sink, queue) = Sink::new_idle();
+            mixer.add(queue);
+            Arc::new(sink)
+        });
+        let stop = Arc::new(AtomicBool::new(false));
+        let thread = thread::Builder::new().name(String::from("output")).spawn({
+            let sinks = sinks.clone();
+            let stop = stop.clone();
+            move || consume(output, &sinks, &stop, writer, realtime, &events)
+        }).map_err(|e| Error::Output(e.to_string()))?;
+        Ok((sinks, OutputHandle::Thread {stop, thread: Some(thread)}))
+    }
+}
+
+// 从混合了所有容器的输出中取出采样，写入文件或直接丢弃，直到句柄被释放
+fn consume(
+    mut samples: DynamicMixer<f32>,
+    sinks: &[Arc<Sink>],
+    stop: &AtomicBool,
+    mut writer: Option<WavWriter<BufWriter<File>>>,
+    realtime: bool,
+    events: &Sender<Event>,
+) {
+    // 每次处理10毫秒的采样
+    let chunk = (SAMPLE_RATE as usize * CHANNELS as usize) / 100;
+    let started = Instant::now();
+    let mut consumed = 0u64;
+    while !stop.load(Ordering::Relaxed) {
+        // 不按真实时间时暂停或播完后不取采样；但容器只在取出采样时处理跳转等控制，
+        // 暂停中跳转时try_seek会一直等待，所以有暂停的歌曲时仍定时取出一段静音并丢弃
+        if !realtime && sinks.iter().all(|sink| sink.empty() || sink.is_paused()) {
+            if sinks.iter().any(|sink| !sink.empty() && sink.is_paused()) {
+                samples.by_ref().take(chunk).for_each(drop);
+            }
+            thread::sleep(Duration::from_millis(5));
+            continue;
+        }
+        let mut count = 0;
+        while count < chunk {
+            // 不按真实时间时，播完或暂停后在下一个完整的帧处停下，不把歌曲之间的静音写进文件
+            if !realtime && count % CHANNELS as usize == 0 && sinks.iter().all(|sink| sink.empty() || sink.is_paused()) {
+                break;
+            }
+            let Some(sample) = samples.next() else {break};
+            if let Some(w) = writer.as_mut() {
+                if w.len() >= WAV_MAX_SAMPLES {
+                    let _ = writer.take().map(WavWriter::finalize);
+                }
+                // 写入失败（如磁盘已满）后不再写入，但仍要继续取出采样，否则跳转、换歌时容器会一直等待
+                else if let Err(e) = w.write_sample((sample.clamp(-1.0, 1.0) * i16::MAX as f32) as i16) {
+                    let _ = writer.take().map(WavWriter::finalize);
+                    let _ = events.send(Event::Error(Error::Output(format!("WAV output stopped: {}", e))));
+                }
+            }
+            count += 1;
+        }
+        consumed += count as u64;
+        if realtime {
+            let due = Duration::from_micros(consumed * 1_000_000 / (SAMPLE_RATE as u64 * CHANNELS as u64));
+            if let Some(wait) = due.checked_sub(started.elapsed()) {
+                thread::sleep(wait);
+            }
+        }
+        else if count == 0 {
+            thread::sleep(Duration::from_millis(5));
+        }
+    }
+    if let Some(writer) = writer {
+        let _ = writer.finalize();
+    }
+}
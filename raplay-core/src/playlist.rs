@@ -0,0 +1,355 @@
+use std::{
+    collections::BTreeMap,
+    fs,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use crate::error::{Error, Result};
+
+// 播放列表中的一项，标题和时长来自#EXTINF、PLS的TitleN/LengthN或XSPF的title/duration
+#[derive(Clone)]
+pub struct PlaylistEntry {
+    pub path: String,
+    pub title: Option<String>,
+    pub duration: Option<Duration>,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum PlaylistFormat {M3u, Pls, Xspf}
+
+impl PlaylistFormat {
+    pub fn from_path(path: &Path) -> Option<Self> {
+        match path.extension()?.to_str()?.to_ascii_lowercase().as_str() {
+            "m3u" | "m3u8" => Some(PlaylistFormat::M3u),
+            "pls" => Some(PlaylistFormat::Pls),
+            "xspf" => Some(PlaylistFormat::Xspf),
+            _ => None,
+        }
+    }
+}
+
+pub fn is_playlist_file(name: &str) -> bool {
+    PlaylistFormat::from_path(Path::new(name)).is_some()
+}
+
+// 读取播放列表，相对路径以播放列表所在的文件夹为基准，网络地址会被忽略
+pub fn load_playlist(path: &Path) -> Result<Vec<PlaylistEntry>> {
+    let format = PlaylistFormat::from_path(path).ok_or_else(|| Error::UnknownPlaylist(path.to_path_buf()))?;
+    let text = String::from_utf8_lossy(&fs::read(path).map_err(Error::io(path))?).into_owned();
+    let base = path.parent().unwrap_or(Path::new("."));
+    let entries = match format {
+        PlaylistFormat::M3u => parse_m3u(&text),
+        PlaylistFormat::Pls => parse_pls(&text),
+        PlaylistFormat::Xspf => parse_xspf(&text),
+    };
+    Ok(entries.into_iter()
+        .filter_map(|mut entry| {
+            entry.path = resolve_location(&entry.path, base)?;
+            Some(entry)
+        })
+        .collect())
+}
+
+// 按扩展名决定格式保存播放列表，位于播放列表所在文件夹之下的歌曲写为相对路径
+pub fn save_playlist(path: &Path, entries: &[PlaylistEntry]) -> Result<()> {
+    let format = PlaylistFormat::from_path(path).ok_or_else(|| Error::UnknownPlaylist(path.to_path_buf()))?;
+    let base = path.parent().unwrap_or(Path::new("."));
+    let text = match format {
+        PlaylistFormat::M3u => write_m3u(entries, base),
+        PlaylistFormat::Pls => write_pls(entries, base),
+        PlaylistFormat::Xspf => write_xspf(entries),
+    };
+    fs::write(path, text).map_err(Error::io(path))
+}
+
+fn parse_m3u(text: &str) -> Vec<PlaylistEntry> {
+    let mut entries = vec![];
+    let mut extinf: Option<(Option<Duration>, Option<String>)> = None;
+    for line in text.lines() {
+        let line = line.trim_start_matches('\u{feff}').trim();
+        if let Some(info) = line.strip_prefix("#EXTINF:") {
+            // #EXTINF:时长[ 属性...],标题
+            let (head, title) = info.split_once(',').unwrap_or((info, ""));
+            let secs = head.split_whitespace().next().and_then(|s| s.parse::<f64>().ok());
+            extinf = Some((
+                secs.filter(|&secs| secs > 0.0).map(Duration::from_secs_f64),
+                Some(title.trim().to_string()).filter(|t| !t.is_empty()),
+            ));
+        }
+        else if !line.is_empty() && !line.starts_with('#') {
+            let (duration, title) = extinf.take().unwrap_or((None, None));
+            entries.push(PlaylistEntry {path: line.to_string(), title, duration});
+        }
+    }
+    entries
+}
+
+fn parse_pls(text: &str) -> Vec<PlaylistEntry> {
+    let mut entries: BTreeMap<u32, PlaylistEntry> = BTreeMap::new();
+    for line in text.lines() {
+        let (key, value) = match line.trim().split_once('=') {
+            Some(pair) => pair,
+            None => continue,
+        };
+        let key = key.trim().to_ascii_lowercase();
+        let value = value.trim();
+        let (field, n) = match ["file", "title", "length"].iter()
+            .find_map(|field| Some((*field, key.strip_prefix(field)?.parse::<u32>().ok()?)))
+        {
+            Some(pair) => pair,
+            None => continue,
+        };
+        let entry = entries.entry(n).or_insert(PlaylistEntry {path: String::new(), title: None, duration: None});
+        match field {
+            "file" => entry.path = value.to_string(),
+            "title" => entry.title = Some(value.to_string()).filter(|t| !t.is_empty()),
+            _ => entry.duration = value.parse::<i64>().ok().filter(|&secs| secs > 0).map(|secs| Duration::from_secs(secs as u64)),
+        }
+    }
+    entries.into_values().filter(|entry| !entry.path.is_empty()).collect()
+}
+
+fn parse_xspf(text: &str) -> Vec<PlaylistEntry> {
+    let mut entries = vec![];
+    let mut rest = text;
+    while let Some(start) = rest.find("<track>") {
+        rest = &rest[start + "<track>".len()..];
+        let end = rest.find("</track>").unwrap_or(rest.len());
+        let track = &rest[..end];
+        rest = &rest[end..];
+        if let Some(location) = xml_element(track, "location") {
+            entries.push(PlaylistEntry {
+                // location是URI，相对路径同样经过百分号编码
+                path: if location.contains("://") {location} else {percent_decode(&location)},
+                title: xml_element(track, "title").filter(|t| !t.is_empty()),
+                duration: xml_element(track, "duration")
+                    .and_then(|ms| ms.parse::<u64>().ok())
+                    .filter(|&ms| ms > 0)
+                    .map(Duration::from_millis),
+            });
+        }
+    }
+    entries
+}
+
+// 取出<name>...</name>中的文本并还原XML实体
+fn xml_element(text: &str, name: &str) -> Option<String> {
+    let open = format!("<{}>", name);
+    let close = format!("</{}>", name);
+    let start = text.find(&open)? + open.len();
+    let end = start + text[start..].find(&close)?;
+    Some(xml_unescape(text[start..end].trim()))
+}
+
+fn xml_unescape(text: &str) -> String {
+    text.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&apos;", "'")
+        .replace("&amp;", "&")
+}
+
+fn xml_escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+// 把播放列表中的位置转换为本地路径，file://地址会被解码，其他网络地址返回None
+fn resolve_location(location: &str, base: &Path) -> Option<String> {
+    let path = if let Some(uri) = location.strip_prefix("file://") {
+        // file:///C:/... 形式在Windows上要去掉开头的斜杠
+        let decoded = percent_decode(uri);
+        if cfg!(windows) && decoded.len() > 2 && decoded.as_bytes()[2] == b':' {
+            PathBuf::from(&decoded[1..])
+        }
+        else {
+            PathBuf::from(decoded)
+        }
+    }
+    else if location.contains("://") {
+        return None;
+    }
+    else if cfg!(windows) {
+        PathBuf::from(location)
+    }
+    else {
+        PathBuf::from(location.replace('\\', "/"))
+    };
+    let path = if path.is_relative() {base.join(path)} else {path};
+    Some(path.to_string_lossy().into_owned())
+}
+
+fn percent_decode(text: &str) -> String {
+    let bytes = text.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let hex = bytes.get(i + 1..i + 3)
+            .and_then(|hex| std::str::from_utf8(hex).ok())
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        if let (b'%', Some(byte)) = (bytes[i], hex) {
+            out.push(byte);
+            i += 3;
+            continue;
+        }
+        out.push(bytes[i]);
+        i += 1;
+    }
+    String::from_utf8_lossy(&out).into_owned()
+}
+
+fn percent_encode(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for &byte in text.as_bytes() {
+        if byte.is_ascii_alphanumeric() || b"/-_.~:".contains(&byte) {
+            out.push(byte as char);
+        }
+        else {
+            out.push_str(&format!("%{:02X}", byte));
+        }
+    }
+    out
+}
+
+// 位于base之下的路径写为相对路径，否则保持原样
+fn relative_path(path: &str, base: &Path) -> String {
+    match Path::new(path).strip_prefix(base) {
+        Ok(rel) => rel.to_string_lossy().into_owned(),
+        Err(_) => path.to_string(),
+    }
+}
+
+// M3U和PLS每项占一行，标题中的换行等控制字符换成空格
+fn single_line(text: &str) -> String {
+    text.chars().map(|c| if c.is_control() {' '} else {c}).collect()
+}
+
+fn write_m3u(entries: &[PlaylistEntry], base: &Path) -> String {
+    let mut text = String::from("#EXTM3U\n");
+    for entry in entries {
+        let secs = entry.duration.map(|dur| dur.as_secs() as i64).unwrap_or(-1);
+        text.push_str(&format!("#EXTINF:{},{}\n", secs, single_line(entry.title.as_deref().unwrap_or(""))));
+        text.push_str(&relative_path(&entry.path, base));
+        text.push('\n');
+    }
+    text
+}
+
+fn write_pls(entries: &[PlaylistEntry], base: &Path) -> String {
+    let mut text = String::from("[playlist]\n");
+    for (i, entry) in entries.iter().enumerate() {
+        let n = i + 1;
+        text.push_str(&format!("File{}={}\n", n, relative_path(&entry.path, base)));
+        if let Some(title) = &entry.title {
+            text.push_str(&format!("Title{}={}\n", n, single_line(title)));
+        }
+        let secs = entry.duration.map(|dur| dur.as_secs() as i64).unwrap_or(-1);
+        text.push_str(&format!("Length{}={}\n", n, secs));
+    }
+    text.push_str(&format!("NumberOfEntries={}\nVersion=2\n", entries.len()));
+    text
+}
+
+fn write_xspf(entries: &[PlaylistEntry]) -> String {
+    let mut text = String::from(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<playlist version=\"1\" xmlns=\"http://xspf.org/ns/0/\">\n  <trackList>\n"
+    );
+    for entry in entries {
+        let path = fs::canonicalize(&entry.path)
+            .map(|p| p.to_string_lossy().into_owned())
+            .unwrap_or_else(|_| entry.path.clone())
+            .replace('\\', "/");
+        let path = if path.starts_with('/') {path} else {format!("/{}", path)};
+        text.push_str("    <track>\n");
+        text.push_str(&format!("      <location>file://{}</location>\n", xml_escape(&percent_encode(&path))));
+        if let Some(title) = &entry.title {
+            text.push_str(&format!("      <title>{}</title>\n", xml_escape(title)));
+        }
+        if let Some(dur) = entry.duration {
+            text.push_str(&format!("      <duration>{}</duration>\n", dur.as_millis()));
+        }
+        text.push_str("    </track>\n");
+    }
+    text.push_str("  </trackList>\n</playlist>\n");
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(path: &str, title: Option<&str>, secs: Option<u64>) -> PlaylistEntry {
+        PlaylistEntry {path: path.to_string(), title: title.map(String::from), duration: secs.map(Duration::from_secs)}
+    }
+
+    fn summary(entries: &[PlaylistEntry]) -> Vec<(String, Option<String>, Option<u64>)> {
+        entries.iter()
+            .map(|e| (e.path.clone(), e.title.clone(), e.duration.map(|d| d.as_secs())))
+            .collect()
+    }
+
+    #[test]
+    fn m3u_reads_extinf() {
+        let text = "\u{feff}#EXTM3U\n#EXTINF:123 tvg-id=\"x\",Artist - Title\nmusic/a.mp3\n\n# comment\n/abs/b.flac\n#EXTINF:-1,\nc.ogg\n";
+        assert_eq!(summary(&parse_m3u(text)), vec![
+            (String::from("music/a.mp3"), Some(String::from("Artist - Title")), Some(123)),
+            (String::from("/abs/b.flac"), None, None),
+            (String::from("c.ogg"), None, None),
+        ]);
+    }
+
+    #[test]
+    fn pls_reads_numbered_entries() {
+        let text = "[playlist]\nTitle2=Second\nFile2=b.mp3\nFile1=a.mp3\nLength1=-1\nLength2=61\nNumberOfEntries=2\nFile3=\n";
+        assert_eq!(summary(&parse_pls(text)), vec![
+            (String::from("a.mp3"), None, None),
+            (String::from("b.mp3"), Some(String::from("Second")), Some(61)),
+        ]);
+    }
+
+    #[test]
+    fn xspf_reads_tracks() {
+        let text = "<playlist><trackList>\
+            <track><location>file:///music/a%20b.mp3</location><title>Rock &amp; Roll</title><duration>61000</duration></track>\
+            <track><location>sub%20dir/c.ogg</location></track>\
+            <track><title>no location</title></track>\
+            </trackList></playlist>";
+        assert_eq!(summary(&parse_xspf(text)), vec![
+            (String::from("file:///music/a%20b.mp3"), Some(String::from("Rock & Roll")), Some(61)),
+            (String::from("sub dir/c.ogg"), None, None),
+        ]);
+    }
+
+    #[test]
+    fn percent_decoding() {
+        assert_eq!(percent_decode("a%20b%E4%BD%A0"), "a b你");
+        assert_eq!(percent_decode("100%zz%"), "100%zz%");
+        assert_eq!(percent_decode(&percent_encode("/m/a b#1.mp3")), "/m/a b#1.mp3");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn locations_resolve_against_base() {
+        let base = Path::new("/lists");
+        assert_eq!(resolve_location("a.mp3", base).as_deref(), Some("/lists/a.mp3"));
+        assert_eq!(resolve_location("sub\\b.mp3", base).as_deref(), Some("/lists/sub/b.mp3"));
+        assert_eq!(resolve_location("/music/c.mp3", base).as_deref(), Some("/music/c.mp3"));
+        assert_eq!(resolve_location("file:///music/a%20b.mp3", base).as_deref(), Some("/music/a b.mp3"));
+        assert_eq!(resolve_location("http://example.com/d.mp3", base), None);
+    }
+
+    #[test]
+    fn titles_stay_on_one_line() {
+        let entries = [entry("/lists/a.mp3", Some("two\nlines\r"), Some(5)), entry("/music/b.mp3", None, None)];
+        let m3u = write_m3u(&entries, Path::new("/lists"));
+        assert_eq!(m3u, "#EXTM3U\n#EXTINF:5,two lines \na.mp3\n#EXTINF:-1,\n/music/b.mp3\n");
+        let pls = write_pls(&entries, Path::new("/lists"));
+        assert_eq!(summary(&parse_pls(&pls)), vec![
+            (String::from("a.mp3"), Some(String::from("two lines")), Some(5)),
+            (String::from("/music/b.mp3"), None, None),
+        ]);
+    }
+}
@@ -0,0 +1,44 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+// 测试用的临时文件夹，释放时连同其中的文件一起删除
+pub struct TempDir(PathBuf);
+
+impl TempDir {
+    // 每次创建的文件夹都不同，并行运行的测试互不影响
+    pub fn new(name: &str) -> Self {
+        static COUNT: AtomicUsize = AtomicUsize::new(0);
+        let id = COUNT.fetch_add(1, Ordering::Relaxed);
+        let dir = std::env::temp_dir().join(format!("raplay-{}-{}-{}", name, std::process::id(), id));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        Self(dir)
+    }
+    pub fn path(&self) -> &Path {
+        &self.0
+    }
+    pub fn join(&self, name: &str) -> PathBuf {
+        self.0.join(name)
+    }
+    // 生成一段单声道16bit的正弦波WAV文件，返回完整路径
+    pub fn sine_wav(&self, name: &str, sample_rate: u32, secs: u32) -> String {
+        let path = self.join(name);
+        let spec = hound::WavSpec {channels: 1, sample_rate, bits_per_sample: 16, sample_format: hound::SampleFormat::Int};
+        let mut writer = hound::WavWriter::create(&path, spec).unwrap();
+        for i in 0..sample_rate * secs {
+            let t = i as f32 / sample_rate as f32;
+            writer.write_sample(((t * 440.0 * std::f32::consts::TAU).sin() * 8000.0) as i16).unwrap();
+        }
+        writer.finalize().unwrap();
+        path.to_string_lossy().into_owned()
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.0);
+    }
+}
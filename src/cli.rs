@@ -0,0 +1,90 @@
+use std::path::PathBuf;
+
+use clap::{Parser, ValueEnum};
+use raplay_core::output::Output;
+
+// 命令行参数，例如：raplay --volume 40 --mode shuffle ~/Music/Album playlist.m3u8
+#[derive(Parser)]
+#[command(version, about = "Local music player in Terminal.")]
+pub struct Args {
+    /// Audio files, folders (added recursively) and playlists to queue and play;
+    /// the file browser starts in the first folder given
+    pub paths: Vec<PathBuf>,
+
+    /// Start volume in percent (0-100), instead of the level saved on exit
+    #[arg(short, long, value_parser = clap::value_parser!(u8).range(0..=100))]
+    pub volume: Option<u8>,
+
+    /// Play mode [default: loop, or once without the interface]
+    #[arg(short, long, value_enum)]
+    pub mode: Option<ModeArg>,
+
+    /// Seed for shuffle, so the same order can be played again
+    #[arg(long)]
+    pub seed: Option<u64>,
+
+    /// Start the first track at this position, e.g. 90 or 1:30
+    #[arg(short, long, value_name = "TIME", value_parser = parse_start)]
+    pub start: Option<u64>,
+
+    /// Read the config from this file instead of the default location
+    #[arg(short, long, value_name = "FILE")]
+    pub config: Option<PathBuf>,
+
+    /// Play the given paths without the terminal interface and exit when done;
+    /// implied when stdout is not a terminal
+    #[arg(long)]
+    pub no_tui: bool,
+
+    /// Report playback as JSON lines on stdout instead of text on stderr (implies --no-tui)
+    #[arg(long)]
+    pub json: bool,
+
+    /// Where to send the audio: "device", "null" to discard it, or a .wav file to record it
+    /// [default: device, or null when there is no audio device]
+    #[arg(short, long, value_name = "OUTPUT", value_parser = parse_output)]
+    pub output: Option<Output>,
+
+    /// With --output null or a .wav file, play as fast as possible instead of in real time
+    #[arg(long, requires = "output")]
+    pub fast: bool,
+
+    /// Crossfade between tracks for this many seconds, 0 to turn it off, instead of the config
+    #[arg(long, value_name = "SECS", value_parser = parse_crossfade)]
+    pub crossfade: Option<f32>,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+pub enum ModeArg {
+    /// Play the queue once
+    Once,
+    /// Repeat the queue
+    Loop,
+    /// Repeat the current track
+    One,
+    /// Repeat the queue in random order
+    Shuffle,
+}
+
+// 开始位置，格式与界面中的时间戳跳转相同，返回毫秒
+fn parse_start(text: &str) -> Result<u64, String> {
+    crate::parse_timestamp(text).ok_or_else(|| String::from("expected seconds or a timestamp like 1:30"))
+}
+
+// 输出位置；是否按真实时间播放由--fast决定，这里先按真实时间
+fn parse_output(text: &str) -> Result<Output, String> {
+    match text {
+        "device" => Ok(Output::Device),
+        "null" => Ok(Output::Null {realtime: true}),
+        _ if text.to_ascii_lowercase().ends_with(".wav") => Ok(Output::Wav {path: PathBuf::from(text), realtime: true}),
+        _ => Err(String::from("expected device, null or a .wav file")),
+    }
+}
+
+// 交叉淡化的秒数，可以有小数
+fn parse_crossfade(text: &str) -> Result<f32, String> {
+    match text.parse::<f32>() {
+        Ok(secs) if secs.is_finite() && (0.0..=30.0).contains(&secs) => Ok(secs),
+        _ => Err(String::from("expected seconds from 0 to 30")),
+    }
+}
@@ -0,0 +1,78 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    error::Error,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use serde::Deserialize;
+
+use raplay_core::fade::FadeCurve;
+
+use crate::{
+    keymap::{Action, Preset},
+    theme::ThemeSpec,
+};
+
+// 用户配置，保存在用户配置目录下的raplay/config.toml，所有项都可省略
+//
+// preset = "vim"              # 按键方案：default、vim、media
+// seek_steps = [5, 30, 60]    # 快进快退的步长（秒），依次对应普通、中、大
+// theme = "nord"              # 启动时的主题：内置的default、mono、nord、gruvbox、ascii或[themes]中定义的主题
+// crossfade = 4               # 歌曲之间交叉淡化的秒数，省略或为0时无缝衔接；同一张无缝专辑内的歌曲之间不淡化
+// crossfade_curve = "linear"  # 淡化曲线：equal_power（默认）、linear
+//
+// [keys]                      # 覆盖方案中的按键，一个操作可以绑定多个按键
+// play_pause = ["space", "p"]
+// seek_forward_large = "ctrl+right"
+//
+// [themes.mine]               # 自定义主题，各项见theme.rs
+// base = "nord"
+// accent = "#ffb86c"
+#[derive(Deserialize, Default)]
+#[serde(default)]
+pub struct Config {
+    pub preset: Preset,
+    pub seek_steps: Option<[u64; 3]>,
+    pub keys: HashMap<Action, KeyList>,
+    pub theme: Option<String>,
+    pub themes: BTreeMap<String, ThemeSpec>,
+    pub crossfade: Option<f32>,
+    pub crossfade_curve: FadeCurve,
+}
+
+// 按键可以写成单个字符串或字符串数组
+#[derive(Deserialize)]
+#[serde(untagged)]
+pub enum KeyList {
+    One(String),
+    Many(Vec<String>),
+}
+
+impl KeyList {
+    pub fn into_vec(self) -> Vec<String> {
+        match self {
+            KeyList::One(key) => vec![key],
+            KeyList::Many(keys) => keys,
+        }
+    }
+}
+
+impl Config {
+    pub fn path() -> Option<PathBuf> {
+        dirs::config_dir().map(|dir| dir.join("raplay").join("config.toml"))
+    }
+    // 读取配置文件，默认位置的文件不存在时返回默认配置，格式错误或指定的文件不存在时返回错误
+    pub fn load(path: Option<&Path>) -> Result<Self, Box<dyn Error>> {
+        let explicit = path.is_some();
+        let path = match path.map(Path::to_path_buf).or_else(Self::path) {
+            Some(path) => path,
+            None => return Ok(Self::default()),
+        };
+        match fs::read_to_string(&path) {
+            Ok(text) => Ok(toml::from_str(&text).map_err(|err| format!("{}: {}", path.display(), err.message()))?),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound && !explicit => Ok(Self::default()),
+            Err(err) => Err(format!("{}: {}", path.display(), err).into()),
+        }
+    }
+}
@@ -0,0 +1,190 @@
+use std::{
+    cell::Cell,
+    collections::HashMap,
+    error::Error,
+    io::{self, Write},
+    sync::mpsc::RecvTimeoutError,
+    time::Duration,
+};
+
+use raplay_core::{
+    engine::{Command, Event, PlayMode, Player, Status},
+    library::Library,
+    metadata::{TrackInfoCache, TrackTags},
+    playlist::PlaylistEntry,
+};
+use serde_json::{json, Value};
+
+use crate::{
+    cli::Args,
+    config::Config,
+    display_name, fmt_hms,
+    startup::{expand_args, spawn_player, start_volume},
+};
+
+// 无界面模式的输出：文本状态行写到stderr，--json时每个事件一行JSON写到stdout
+struct Reporter {
+    json: bool,
+    closed: Cell<bool>,     // stdout的读取端已关闭
+}
+
+impl Reporter {
+    fn event(&self, event: Value, text: impl FnOnce() -> Option<String>) {
+        if self.json {
+            let mut out = io::stdout().lock();
+            // 读取端已关闭（如管道后面的head退出）时没有必要继续播放，
+            // 由事件循环正常结束，让播放线程退出并写好WAV文件头
+            if self.closed.get() || writeln!(out, "{}", event).and_then(|_| out.flush()).is_err() {
+                self.closed.set(true);
+            }
+        }
+        else if let Some(text) = text() {
+            eprintln!("{}", text);
+        }
+    }
+    fn error(&self, message: &str) {
+        self.event(json!({"event": "error", "message": message}), || Some(message.to_string()));
+    }
+}
+
+// 无界面模式的状态：只保留报告歌名和时长需要的信息，不使用界面的App
+struct Headless {
+    player: Player,
+    queue_len: usize,                           // 播放队列中的歌曲数
+    tags: HashMap<String, TrackTags>,           // 按路径缓存的标签信息
+    hints: HashMap<String, PlaylistEntry>,      // 播放列表文件中记录的标题和时长
+    track_info: TrackInfoCache,
+    skipped: usize,
+}
+
+impl Headless {
+    fn display_name(&mut self, path: &str) -> String {
+        let tags = self.tags.entry(path.to_string()).or_insert_with(|| TrackTags::read(path));
+        display_name(Some(tags), self.hints.get(path), path)
+    }
+}
+
+// 无界面模式：播放命令行中指定的歌曲，不需要终端，播完后退出
+pub fn run_headless(args: &Args) -> Result<(), Box<dyn Error>> {
+    let reporter = Reporter {json: args.json, closed: Cell::new(false)};
+    let config = Config::load(args.config.as_deref()).unwrap_or_else(|err| {
+        reporter.error(&err.to_string());
+        Config::default()
+    });
+    let library = Library::load().unwrap_or_else(|err| {
+        reporter.error(&format!("Library index unreadable, kept as .bak: {}", err));
+        Library::default()
+    });
+    // 默认只播放一次，播完即退出
+    let mode = args.mode.map(PlayMode::from).unwrap_or(PlayMode::ListOnce);
+    let (player, warning) = spawn_player(args, &config, mode, start_volume(args))?;
+    if let Some(warning) = warning {
+        reporter.error(&warning);
+    }
+    let expanded = expand_args(&args.paths, &library);
+    if expanded.missing > 0 {
+        reporter.error(&format!("{} missing file(s)", expanded.missing));
+    }
+    if expanded.skipped > 0 {
+        reporter.error(&format!("{} file(s) with non-UTF-8 names skipped", expanded.skipped));
+    }
+    if !expanded.failed.is_empty() {
+        reporter.error(&format!("Cannot open: {}", expanded.failed.join(", ")));
+    }
+    let count = expanded.tracks.len();
+    if count == 0 {
+        return Err("nothing to play".into());
+    }
+    let mut state = Headless {
+        player,
+        queue_len: count,
+        tags: library.tracks.iter().map(|(path, track)| (path.clone(), track.tags.clone())).collect(),
+        hints: expanded.hints.into_iter().map(|entry| (entry.path.clone(), entry)).collect(),
+        track_info: TrackInfoCache::default(),
+        skipped: 0,
+    };
+    // 随机模式下第一首由播放线程随机选取
+    state.player.send(Command::Replace(expanded.tracks, None));
+    if let Some(msec) = args.start {
+        state.player.send(Command::Seek(msec));
+    }
+    reporter.event(json!({"event": "start", "tracks": count}), || None);
+    let mut played = 0;
+    let mut last_pos = 0;
+    while !reporter.closed.get() {
+        state.track_info.update();
+        let event = match state.player.events.recv_timeout(Duration::from_millis(100)) {
+            Ok(event) => event,
+            Err(RecvTimeoutError::Timeout) => continue,
+            Err(RecvTimeoutError::Disconnected) => return Err("player stopped".into()),
+        };
+        match event {
+            Event::Queue(list) => state.queue_len = list.len(),
+            // 每载入一首（包括单曲循环、重新播放）都报告一次
+            Event::Loaded {id, path, duration, channels, sample_rate} => {
+                let hint = state.hints.get(&path).and_then(|entry| entry.duration);
+                state.track_info.insert(&path, duration, hint, channels, sample_rate);
+                played += 1;
+                last_pos = 0;
+                report_track(&mut state, &reporter, id, &path);
+            },
+            Event::State(player) if player.status == Status::Playing => {
+                let pos = player.position.as_millis() as u64;
+                if pos / 1000 != last_pos / 1000 {
+                    let dur = state.track_info.duration(&player.path).map(|dur| dur.as_millis() as u64);
+                    reporter.event(
+                        json!({"event": "progress", "index": player.current, "position_ms": pos, "duration_ms": dur}),
+                        || None,
+                    );
+                }
+                last_pos = pos;
+            },
+            Event::Skipped {id, path, error} => {
+                state.skipped += 1;
+                let name = state.display_name(&path);
+                let total = state.queue_len;
+                reporter.event(
+                    json!({"event": "skipped", "index": id, "path": path, "error": error.to_string()}),
+                    || Some(format!("[{}/{}] {} skipped: {}", id, total, name, error)),
+                );
+            },
+            Event::Error(error) => reporter.error(&error.to_string()),
+            Event::Message(msg) => reporter.error(&msg),
+            Event::Finished => break,
+            _ => {},
+        }
+    }
+    state.player.send(Command::Quit);
+    let skipped = state.skipped;
+    reporter.event(
+        json!({"event": "end", "played": played, "skipped": skipped}),
+        || Some(match skipped {
+            0 => format!("Finished, {} track(s) played", played),
+            _ => format!("Finished, {} track(s) played, {} skipped", played, skipped),
+        }),
+    );
+    Ok(())
+}
+
+fn report_track(state: &mut Headless, reporter: &Reporter, id: u16, path: &str) {
+    let name = state.display_name(path);
+    let tags = state.tags.get(path).cloned().unwrap_or_default();
+    let total = state.queue_len;
+    let dur = state.track_info.duration(path).map(|dur| dur.as_millis() as u64);
+    reporter.event(
+        json!({
+            "event": "track",
+            "index": id,
+            "total": total,
+            "path": path,
+            "title": tags.title,
+            "artist": tags.artist,
+            "album": tags.album,
+            "duration_ms": dur,
+        }),
+        || Some(match dur {
+            Some(dur) => format!("[{}/{}] {} ({})", id, total, name, fmt_hms(dur / 1000)),
+            None => format!("[{}/{}] {}", id, total, name),
+        }),
+    );
+}
@@ -0,0 +1,399 @@
+use std::collections::HashMap;
+
+use ratatui::crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use serde::Deserialize;
+
+// 所有可绑定按键的操作，与具体按键无关；配置文件中使用snake_case的名称
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Action {
+    Quit,
+    PlayPause,
+    NextTrack,
+    PrevTrack,
+    Restart,
+    CycleMode,
+    SeekForward,
+    SeekBackward,
+    SeekForwardMedium,
+    SeekBackwardMedium,
+    SeekForwardLarge,
+    SeekBackwardLarge,
+    ToggleRemaining,
+    VolumeUp,
+    VolumeDown,
+    VolumeUpCoarse,
+    VolumeDownCoarse,
+    ToggleMute,
+    Up,
+    Down,
+    Open,
+    Parent,
+    SwitchFocus,
+    PlayFolder,
+    PlayFile,
+    LoadFolder,
+    Enqueue,
+    EnqueueNext,
+    Remove,
+    MoveUp,
+    MoveDown,
+    ClearQueue,
+    Dedupe,
+    AddLibraryRoot,
+    RescanLibrary,
+    SavePlaylist,
+    Help,
+    CycleTheme,
+}
+
+// 帮助界面中的分类，每个操作恰好出现一次
+pub const CATEGORIES: [(&str, &[Action]); 6] = [
+    ("Playback", &[
+        Action::PlayPause, Action::NextTrack, Action::PrevTrack, Action::Restart, Action::CycleMode,
+    ]),
+    ("Seeking", &[
+        Action::SeekForward, Action::SeekBackward, Action::SeekForwardMedium, Action::SeekBackwardMedium,
+        Action::SeekForwardLarge, Action::SeekBackwardLarge, Action::ToggleRemaining,
+    ]),
+    ("Volume", &[
+        Action::VolumeUp, Action::VolumeDown, Action::VolumeUpCoarse, Action::VolumeDownCoarse, Action::ToggleMute,
+    ]),
+    ("Browser", &[
+        Action::Up, Action::Down, Action::Open, Action::Parent, Action::SwitchFocus,
+        Action::PlayFolder, Action::PlayFile, Action::LoadFolder, Action::Enqueue, Action::EnqueueNext,
+    ]),
+    ("Queue & library", &[
+        Action::Remove, Action::MoveUp, Action::MoveDown, Action::ClearQueue, Action::Dedupe,
+        Action::SavePlaylist, Action::AddLibraryRoot, Action::RescanLibrary,
+    ]),
+    ("General", &[
+        Action::Help, Action::CycleTheme, Action::Quit,
+    ]),
+];
+
+impl Action {
+    // 按住不放时是否重复触发
+    pub fn repeatable(self) -> bool {
+        matches!(self,
+            Action::SeekForward | Action::SeekBackward | Action::SeekForwardMedium | Action::SeekBackwardMedium
+            | Action::SeekForwardLarge | Action::SeekBackwardLarge
+            | Action::VolumeUp | Action::VolumeDown | Action::VolumeUpCoarse | Action::VolumeDownCoarse
+            | Action::Up | Action::Down | Action::MoveUp | Action::MoveDown
+        )
+    }
+    pub fn label(self) -> &'static str {
+        match self {
+            Action::Quit => "Quit",
+            Action::PlayPause => "Play/Pause",
+            Action::NextTrack => "Next track",
+            Action::PrevTrack => "Previous track",
+            Action::Restart => "Restart track",
+            Action::CycleMode => "Cycle play mode",
+            Action::SeekForward => "Seek forward",
+            Action::SeekBackward => "Seek backward",
+            Action::SeekForwardMedium => "Seek forward (medium)",
+            Action::SeekBackwardMedium => "Seek backward (medium)",
+            Action::SeekForwardLarge => "Seek forward (large)",
+            Action::SeekBackwardLarge => "Seek backward (large)",
+            Action::ToggleRemaining => "Elapsed/remaining time",
+            Action::VolumeUp => "Volume up",
+            Action::VolumeDown => "Volume down",
+            Action::VolumeUpCoarse => "Volume up (coarse)",
+            Action::VolumeDownCoarse => "Volume down (coarse)",
+            Action::ToggleMute => "Mute",
+            Action::Up => "Select previous",
+            Action::Down => "Select next",
+            Action::Open => "Open/play selected",
+            Action::Parent => "Parent folder",
+            Action::SwitchFocus => "Switch browser/queue",
+            Action::PlayFolder => "Play folder recursively",
+            Action::PlayFile => "Play selected file only",
+            Action::LoadFolder => "Queue current folder",
+            Action::Enqueue => "Add to queue",
+            Action::EnqueueNext => "Play next",
+            Action::Remove => "Remove from queue",
+            Action::MoveUp => "Move up in queue",
+            Action::MoveDown => "Move down in queue",
+            Action::ClearQueue => "Clear queue",
+            Action::Dedupe => "Remove duplicates",
+            Action::AddLibraryRoot => "Add folder to library",
+            Action::RescanLibrary => "Rescan library",
+            Action::SavePlaylist => "Save queue as playlist",
+            Action::Help => "Show/hide this help",
+            Action::CycleTheme => "Next colour theme",
+        }
+    }
+}
+
+// 一个按键组合：按键本身加上Ctrl、Alt、Shift修饰键
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub struct KeyChord {
+    pub code: KeyCode,
+    pub modifiers: KeyModifiers,
+}
+
+impl KeyChord {
+    // 字符键的Shift已经体现在大小写或符号上，统一去掉，避免终端之间的差异
+    fn new(code: KeyCode, modifiers: KeyModifiers) -> Self {
+        let modifiers = modifiers & (KeyModifiers::CONTROL | KeyModifiers::ALT | KeyModifiers::SHIFT);
+        match code {
+            KeyCode::Char(c) => Self {code: KeyCode::Char(c), modifiers: modifiers - KeyModifiers::SHIFT},
+            KeyCode::BackTab => Self {code, modifiers: modifiers - KeyModifiers::SHIFT},
+            _ => Self {code, modifiers},
+        }
+    }
+    pub fn from_event(key: &KeyEvent) -> Self {
+        Self::new(key.code, key.modifiers)
+    }
+    // 解析形如"q"、"space"、"ctrl+left"、"shift+d"的按键描述
+    pub fn parse(text: &str) -> Option<Self> {
+        let mut modifiers = KeyModifiers::NONE;
+        let mut parts = text.split('+').collect::<Vec<_>>();
+        // "+"键本身或以"+"结尾的组合，如"ctrl++"
+        let key = if text.ends_with('+') && text.len() > 1 || text == "+" {
+            parts.retain(|part| !part.is_empty());
+            "+"
+        }
+        else {
+            parts.pop()?
+        };
+        for part in parts {
+            modifiers |= match part.to_ascii_lowercase().as_str() {
+                "ctrl" | "control" => KeyModifiers::CONTROL,
+                "alt" | "meta" => KeyModifiers::ALT,
+                "shift" => KeyModifiers::SHIFT,
+                _ => return None,
+            };
+        }
+        let code = match key.to_ascii_lowercase().as_str() {
+            "space" => KeyCode::Char(' '),
+            "enter" | "return" => KeyCode::Enter,
+            "esc" | "escape" => KeyCode::Esc,
+            "tab" => KeyCode::Tab,
+            "backtab" => KeyCode::BackTab,
+            "backspace" => KeyCode::Backspace,
+            "delete" | "del" => KeyCode::Delete,
+            "insert" | "ins" => KeyCode::Insert,
+            "home" => KeyCode::Home,
+            "end" => KeyCode::End,
+            "pageup" | "pgup" => KeyCode::PageUp,
+            "pagedown" | "pgdn" => KeyCode::PageDown,
+            "up" => KeyCode::Up,
+            "down" => KeyCode::Down,
+            "left" => KeyCode::Left,
+            "right" => KeyCode::Right,
+            lower => {
+                let mut chars = key.chars();
+                match (chars.next(), chars.next()) {
+                    (Some(c), None) if modifiers.contains(KeyModifiers::SHIFT) => {
+                        KeyCode::Char(c.to_ascii_uppercase())
+                    },
+                    (Some(c), None) => KeyCode::Char(c),
+                    _ => KeyCode::F(lower.strip_prefix('f')?.parse().ok().filter(|n| (1..=24).contains(n))?),
+                }
+            },
+        };
+        Some(Self::new(code, modifiers))
+    }
+    pub fn display(&self) -> String {
+        let mut text = String::new();
+        if self.modifiers.contains(KeyModifiers::CONTROL) {
+            text.push_str("Ctrl+");
+        }
+        if self.modifiers.contains(KeyModifiers::ALT) {
+            text.push_str("Alt+");
+        }
+        if self.modifiers.contains(KeyModifiers::SHIFT) {
+            text.push_str("Shift+");
+        }
+        match self.code {
+            KeyCode::Char(' ') => text.push_str("Space"),
+            KeyCode::Char(c) => text.push(c),
+            KeyCode::F(n) => text.push_str(&format!("F{}", n)),
+            KeyCode::Up => text.push('↑'),
+            KeyCode::Down => text.push('↓'),
+            KeyCode::Left => text.push('←'),
+            KeyCode::Right => text.push('→'),
+            KeyCode::PageUp => text.push_str("PgUp"),
+            KeyCode::PageDown => text.push_str("PgDn"),
+            code => text.push_str(&format!("{:?}", code)),
+        }
+        text
+    }
+}
+
+// 内置的按键方案
+#[derive(Clone, Copy, PartialEq, Deserialize, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum Preset {
+    #[default]
+    Default,
+    Vim,
+    Media,
+}
+
+impl Preset {
+    fn bindings(self) -> Vec<(Action, &'static [&'static str])> {
+        let common: Vec<(Action, &'static [&'static str])> = vec![
+            (Action::Quit, &["q"]),
+            (Action::SwitchFocus, &["tab"]),
+            (Action::Open, &["enter"]),
+            (Action::Restart, &["r"]),
+            (Action::ToggleRemaining, &["t"]),
+            (Action::AddLibraryRoot, &["a"]),
+            (Action::RescanLibrary, &["u"]),
+            (Action::SavePlaylist, &["w"]),
+            (Action::Dedupe, &["D"]),
+            (Action::Help, &["?", "f1"]),
+            (Action::CycleTheme, &["y"]),
+        ];
+        let preset: Vec<(Action, &'static [&'static str])> = match self {
+            Preset::Default => vec![
+                (Action::PlayPause, &["p"]),
+                (Action::NextTrack, &["n"]),
+                (Action::PrevTrack, &["b"]),
+                (Action::CycleMode, &["m"]),
+                (Action::SeekForward, &["right"]),
+                (Action::SeekBackward, &["left"]),
+                (Action::SeekForwardMedium, &["shift+right"]),
+                (Action::SeekBackwardMedium, &["shift+left"]),
+                (Action::SeekForwardLarge, &["ctrl+right"]),
+                (Action::SeekBackwardLarge, &["ctrl+left"]),
+                (Action::VolumeUp, &["="]),
+                (Action::VolumeDown, &["-"]),
+                (Action::VolumeUpCoarse, &["+"]),
+                (Action::VolumeDownCoarse, &["_"]),
+                (Action::ToggleMute, &["x"]),
+                (Action::Up, &["up"]),
+                (Action::Down, &["down"]),
+                (Action::Parent, &["backspace"]),
+                (Action::PlayFolder, &["f"]),
+                (Action::PlayFile, &["o"]),
+                (Action::LoadFolder, &["l"]),
+                (Action::Enqueue, &["e"]),
+                (Action::EnqueueNext, &["i"]),
+                (Action::Remove, &["d", "delete"]),
+                (Action::MoveUp, &["shift+up"]),
+                (Action::MoveDown, &["shift+down"]),
+                (Action::ClearQueue, &["c"]),
+            ],
+            // hjkl移动和快进快退，大写字母为加强版本，Ctrl+左右方向键大步快进快退
+            Preset::Vim => vec![
+                (Action::PlayPause, &["space", "p"]),
+                (Action::NextTrack, &["n"]),
+                (Action::PrevTrack, &["N"]),
+                (Action::CycleMode, &["m"]),
+                (Action::SeekForward, &["l"]),
+                (Action::SeekBackward, &["h"]),
+                (Action::SeekForwardMedium, &["L"]),
+                (Action::SeekBackwardMedium, &["H"]),
+                (Action::SeekForwardLarge, &["ctrl+right"]),
+                (Action::SeekBackwardLarge, &["ctrl+left"]),
+                (Action::VolumeUp, &["="]),
+                (Action::VolumeDown, &["-"]),
+                (Action::VolumeUpCoarse, &["+"]),
+                (Action::VolumeDownCoarse, &["_"]),
+                (Action::ToggleMute, &["M"]),
+                (Action::Up, &["k", "up"]),
+                (Action::Down, &["j", "down"]),
+                (Action::Parent, &["backspace"]),
+                (Action::PlayFolder, &["f"]),
+                (Action::PlayFile, &["o"]),
+                (Action::LoadFolder, &["O"]),
+                (Action::Enqueue, &["a"]),
+                (Action::EnqueueNext, &["i"]),
+                (Action::Remove, &["x", "d"]),
+                (Action::MoveUp, &["K"]),
+                (Action::MoveDown, &["J"]),
+                (Action::ClearQueue, &["C"]),
+                (Action::AddLibraryRoot, &["ctrl+a"]),
+            ],
+            // 常见播放器的习惯：空格播放暂停，方向键快进快退，Ctrl+上下调节音量
+            Preset::Media => vec![
+                (Action::PlayPause, &["space"]),
+                (Action::NextTrack, &["n", "pagedown"]),
+                (Action::PrevTrack, &["p", "pageup"]),
+                (Action::CycleMode, &["l"]),
+                (Action::SeekForward, &["right"]),
+                (Action::SeekBackward, &["left"]),
+                (Action::SeekForwardMedium, &["shift+right"]),
+                (Action::SeekBackwardMedium, &["shift+left"]),
+                (Action::SeekForwardLarge, &["ctrl+right"]),
+                (Action::SeekBackwardLarge, &["ctrl+left"]),
+                (Action::VolumeUp, &["ctrl+up", "="]),
+                (Action::VolumeDown, &["ctrl+down", "-"]),
+                (Action::VolumeUpCoarse, &["+"]),
+                (Action::VolumeDownCoarse, &["_"]),
+                (Action::ToggleMute, &["m"]),
+                (Action::Up, &["up"]),
+                (Action::Down, &["down"]),
+                (Action::Parent, &["backspace"]),
+                (Action::PlayFolder, &["f"]),
+                (Action::PlayFile, &["o"]),
+                (Action::LoadFolder, &["ctrl+o"]),
+                (Action::Enqueue, &["e", "insert"]),
+                (Action::EnqueueNext, &["shift+insert"]),
+                (Action::Remove, &["delete"]),
+                (Action::MoveUp, &["shift+up"]),
+                (Action::MoveDown, &["shift+down"]),
+                (Action::ClearQueue, &["ctrl+delete"]),
+            ],
+        };
+        // 方案中重新绑定过的操作不再使用通用的按键
+        let mut all = common.into_iter()
+            .filter(|(action, _)| !preset.iter().any(|(a, _)| a == action))
+            .collect::<Vec<_>>();
+        all.extend(preset);
+        all
+    }
+}
+
+// 按键到操作的映射，同时保留每个操作的按键顺序用于显示
+pub struct KeyMap {
+    actions: HashMap<KeyChord, Action>,
+    keys: HashMap<Action, Vec<KeyChord>>,
+}
+
+impl KeyMap {
+    // 以方案为基础，再用配置文件中的[keys]覆盖；返回无法识别的按键描述
+    pub fn new(preset: Preset, overrides: &HashMap<Action, Vec<String>>) -> (Self, Vec<String>) {
+        let mut keymap = Self {actions: HashMap::new(), keys: HashMap::new()};
+        let mut invalid = vec![];
+        for (action, keys) in preset.bindings() {
+            if !overrides.contains_key(&action) {
+                for key in keys {
+                    keymap.bind(KeyChord::parse(key).expect("built-in key binding"), action);
+                }
+            }
+        }
+        for (&action, keys) in overrides {
+            for key in keys {
+                match KeyChord::parse(key) {
+                    Some(chord) => keymap.bind(chord, action),
+                    None => invalid.push(key.clone()),
+                }
+            }
+        }
+        (keymap, invalid)
+    }
+    // 同一按键只对应一个操作，后绑定的覆盖先绑定的
+    fn bind(&mut self, chord: KeyChord, action: Action) {
+        if let Some(old) = self.actions.insert(chord, action) {
+            if let Some(keys) = self.keys.get_mut(&old) {
+                keys.retain(|key| *key != chord);
+            }
+        }
+        self.keys.entry(action).or_default().push(chord);
+    }
+    pub fn action(&self, key: &KeyEvent) -> Option<Action> {
+        self.actions.get(&KeyChord::from_event(key)).copied()
+    }
+    pub fn keys(&self, action: Action) -> &[KeyChord] {
+        self.keys.get(&action).map(|keys| keys.as_slice()).unwrap_or(&[])
+    }
+    // 操作的第一个按键，用于简短的说明
+    pub fn key_name(&self, action: Action) -> String {
+        self.keys(action).first().map(|key| key.display()).unwrap_or_else(|| String::from("-"))
+    }
+}
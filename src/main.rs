@@ -1,308 +1,1404 @@
 use std::{
-    io::{self, BufReader},
-    path::PathBuf,
-    fs::{File, read_dir},
-    time::Duration,
+    io::{self, IsTerminal},
+    path::{Path, PathBuf},
+    fs::read_dir,
+    time::{Duration, Instant},
     error::Error,
-    sync::mpsc::channel,
+    collections::HashMap,
+    ops::Range,
+    panic,
+    sync::mpsc::{channel, Receiver, TryRecvError},
     thread,
 };
 
 use ratatui::{
     backend::{Backend, CrosstermBackend},
     crossterm::{
+        cursor::Show,
         execute,
-        event::{self, Event, KeyCode, KeyEventKind},
+        event::{
+            self, Event, KeyCode, KeyEventKind, KeyModifiers,
+            MouseButton, MouseEvent, MouseEventKind,
+            EnableMouseCapture, DisableMouseCapture,
+        },
         terminal::{
             enable_raw_mode, disable_raw_mode,
             EnterAlternateScreen, LeaveAlternateScreen
         },
     },
     terminal::{Frame, Terminal},
-    layout::Rect,
-    widgets::{Block, Paragraph}
+    layout::{Constraint, Layout, Margin, Position, Rect},
+    text::{Line, Span},
+    style::Style,
+    symbols,
+    widgets::{Block, Clear, List, ListItem, ListState, Paragraph}
+};
+mod state;
+mod config;
+mod keymap;
+mod theme;
+mod cli;
+mod headless;
+mod startup;
+
+use raplay_core::{
+    engine::{Command, Event as PlayerEvent, PlayMode, Player, Status},
+    library::{is_audio_file, Library, ScanStats},
+    metadata::{TrackInfoCache, TrackTags},
+    playlist::{is_playlist_file, save_playlist, PlaylistEntry, PlaylistFormat},
 };
-use rodio::{Decoder, OutputStream, Sink, Source};
+use state::SavedState;
+use config::Config;
+use keymap::{Action, KeyMap};
+use theme::Theme;
+use cli::{Args, ModeArg};
+use clap::Parser;
+use headless::run_headless;
+use startup::{expand_args, folder_tracks, playlist_tracks, spawn_player, start_volume};
 
 fn main() -> Result<(), Box<dyn Error>> {
+    let args = Args::parse();
+    // 标准输出不是终端时（管道、cron、没有分配TTY的ssh）同样以无界面模式运行
+    if args.no_tui || args.json || !io::stdout().is_terminal() {
+        return run_headless(&args);
+    }
+    // 先打开音频设备，失败时终端还没有进入原始模式
+    let app = App::new(&args)?;
+    // 界面线程崩溃时先恢复终端，否则错误信息会显示在备用屏幕上并且终端停留在原始模式；
+    // 其他线程（播放、输出、计算时长）崩溃时界面仍在运行，不能恢复，播放线程退出由run_app发现并返回错误
+    let main_thread = thread::current().id();
+    let default_hook = panic::take_hook();
+    panic::set_hook(Box::new(move |info| {
+        if thread::current().id() == main_thread {
+            restore_terminal();
+        }
+        default_hook(info);
+    }));
     enable_raw_mode()?;
     let backend = CrosstermBackend::new(io::stdout());
     let mut terminal = Terminal::new(backend)?;
-    execute!(terminal.backend_mut(), EnterAlternateScreen)?;
+    execute!(terminal.backend_mut(), EnterAlternateScreen, EnableMouseCapture)?;
+
+    let result = run_app(&mut terminal, app, &args);
+
+    restore_terminal();
+    result
+}
 
-    let app = App::new();
-    let _ = run_app(&mut terminal, app);
+fn restore_terminal() {
+    let _ = execute!(io::stdout(), DisableMouseCapture, LeaveAlternateScreen, Show);
+    let _ = disable_raw_mode();
+}
+
+// P键作为自锁开关控制播放或暂停，R键将当前歌曲从头开始播放
+// N键、B键分别切换到下一首、上一首，输入数字后按Enter跳转到列表中的第几首
+// 上下方向键在文件浏览器中选择，Enter进入文件夹或从所选歌曲开始播放该文件夹，Backspace返回上一级
+// F键递归播放所选文件夹内的所有音频文件，O键只播放所选的单个文件，L键把当前文件夹载入播放列表
+// 在文件浏览器中对M3U/M3U8、PLS、XSPF播放列表按Enter即可打开，W键把当前播放列表另存为这些格式之一
+// A键把当前文件夹添加到音乐库，U键增量重新扫描音乐库；音乐库内的文件夹直接从库索引读取
+// E键把所选条目加入播放队列末尾，I键把所选条目插到当前歌曲之后；Tab键在文件浏览器和播放队列之间切换焦点
+// 播放队列中Enter播放所选歌曲，D键或Delete移除，Shift+上下方向键调整顺序，C键清空，Shift+D去除重复项
+// 鼠标：点击或拖动进度条跳转，在音量条上滚动滚轮调整音量，单击列表选中、双击播放或进入
+// T键切换显示已播放时间或剩余时间，?键或F1打开帮助界面，列出所有操作及其按键，Y键切换配色主题
+// =/-键以2%调整音量，+/_键以10%调整音量，X键静音或恢复静音前的音量
+// 左右方向键快退快进，按住Shift、Ctrl时步长加大；输入形如1:23:45的时间戳后按Enter跳转到该时间
+// M键控制播放模式：仅顺序播放一次，列表循环，单曲循环，列表循环且随机播放
+// 以上为默认按键，可在配置文件中选择vim或media按键方案，或逐项重新绑定，详见config.rs
+// 命令行参数可指定要播放的文件、文件夹和播放列表以及初始音量、播放模式等，详见cli.rs
+// --no-tui或没有终端时不显示界面，播放状态以文本行写到stderr，--json时以JSON行写到stdout，详见headless.rs
+// 播放线程、音乐库、标签、格式信息和时长以及播放列表位于raplay-core库中，界面只发送命令并根据事件更新显示
+// 无法播放的歌曲会被自动跳过，原因显示在提示行，播放模式一行显示已跳过的数量
+
+// 提示信息显示的时长
+const STATUS_TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(PartialEq)]
+enum Focus {Browser, Queue}
 
-    execute!(terminal.backend_mut(), LeaveAlternateScreen)?;
-    disable_raw_mode()?;
-    terminal.show_cursor()?;
-    Ok(())
+// 上一次绘制时各控件所在的区域，用于把鼠标坐标对应到控件上
+#[derive(Default, Clone, Copy)]
+struct UiAreas {
+    progress: Rect,     // 进度条
+    volume: Rect,       // 音量条
+    browser: Rect,      // 文件浏览器的列表部分（不含边框）
+    queue: Rect,        // 播放队列的列表部分（不含边框）
+    help: Rect,         // 帮助界面的内容部分（不含边框）
 }
 
-// P键作为自锁开关控制播放或暂停，暂停模式下长按R键将播放进度重置
-// L键控制播放模式：仅顺序播放一次，列表循环，单曲循环，列表循环且随机播放
+impl From<ModeArg> for PlayMode {
+    fn from(mode: ModeArg) -> Self {
+        match mode {
+            ModeArg::Once => PlayMode::ListOnce,
+            ModeArg::Loop => PlayMode::LoopAll,
+            ModeArg::One => PlayMode::LoopOne,
+            ModeArg::Shuffle => PlayMode::LoopRnd,
+        }
+    }
+}
 
-enum PlayState {Play(bool), Pause(bool), Restart}
-enum PlayMode {ListOnce, LoopAll, LoopOne, LoopRnd}
+// 后台扫描音乐库的结果：扫描后的音乐库、增量统计和保存索引的结果
+type LibraryScan = (Library, ScanStats, raplay_core::error::Result<()>);
 
 struct AudioFileList {
     dirs: Vec<String>,
+    playlists: Vec<String>,
     files: Vec<String>,
 }
 
+// 文件浏览器中的一项，依次排列为".."、文件夹、播放列表文件、音频文件
+enum BrowserEntry {
+    Parent,
+    Dir(String),
+    Playlist(String),
+    File(usize),
+}
+
 // App目前是必须依赖AudioFileList，以后有可能会抽时间做这块的优化。
 impl AudioFileList {
     fn new() -> Self {
         Self {
             dirs: vec![],
+            playlists: vec![],
             files: vec![],
         }
     }
+    fn insert_dir(&mut self, dir_name: String) {
+        self.dirs.push(dir_name);
+    }
+    fn insert_playlist(&mut self, file_name: String) {
+        self.playlists.push(file_name);
+    }
     fn insert_file(&mut self, file_name: String) {
         self.files.push(file_name);
     }
     fn reset(&mut self) {
+        self.dirs.clear();
+        self.playlists.clear();
         self.files.clear();
     }
+    // 浏览器中的条目数，第一项固定为".."
+    fn len(&self) -> usize {
+        1 + self.dirs.len() + self.playlists.len() + self.files.len()
+    }
+    fn entry(&self, i: usize) -> BrowserEntry {
+        let dirs = self.dirs.len();
+        let playlists = self.playlists.len();
+        if i == 0 {
+            BrowserEntry::Parent
+        }
+        else if i <= dirs {
+            BrowserEntry::Dir(self.dirs[i - 1].clone())
+        }
+        else if i <= dirs + playlists {
+            BrowserEntry::Playlist(self.playlists[i - dirs - 1].clone())
+        }
+        else {
+            BrowserEntry::File(i - dirs - playlists - 1)
+        }
+    }
 }
 
 struct App {
+    player: Player,                 // 播放线程的句柄，用于发送命令和接收事件
     audio_path: String,             // 当前播放的音频文件路径，初始化为空
-    audio_sink: Sink,               // 当前播放的音频文件容器
     song_name: String,              // 当前播放的音频文件名称，初始化为空
     song_curr_time: String,         // 当前播放的音频文件实时时间，初始化为空
     song_duration: String,          // 当前播放的音频文件总时长，初始化为空
-    song_progress: String,          // 当前播放的音频文件实时进度
-    play_state: PlayState,          // 播放状态
-    audio_file_list: AudioFileList, // 用来获取文件位置
-    curr_folderpath: PathBuf,       // 当前的播放列表的文件夹路径，初始化为程序目录
-    curr_playlist: Vec<String>,     // 当前的播放列表，含所有推测的音频文件名称，有且至少要有一项是作为上一级目录的接口
+    song_progress: Option<f64>,     // 当前播放的音频文件实时进度（0.0 ~ 1.0），总时长未知时为None
+    show_remaining: bool,           // 时间显示为已播放时间还是剩余时间
+    status: Status,                 // 播放状态
+    position: Duration,             // 当前播放位置
+    play_mode: PlayMode,            // 播放模式，初始化为列表循环
+    audio_file_list: AudioFileList, // 文件浏览器当前文件夹内的子文件夹和音频文件
+    curr_folderpath: PathBuf,       // 文件浏览器当前所在的文件夹路径，初始化为程序目录
+    browser_selected: usize,        // 文件浏览器中选中的条目，0为上一级目录".."
+    curr_playlist: Vec<String>,     // 当前的播放队列，含所有音频文件的完整路径；队列、编号、路径、状态等都是播放线程状态的副本
+    queue_selected: usize,          // 播放队列中选中的条目（从0开始）
+    focus: Focus,                   // 方向键、Enter等按键作用于文件浏览器还是播放队列
+    browser_offset: usize,          // 文件浏览器滚动到的位置，由绘制时更新
+    queue_offset: usize,            // 播放队列滚动到的位置，由绘制时更新
+    ui_areas: UiAreas,              // 上一次绘制时各控件的区域
+    last_click: Option<(Instant, u16, u16)>,                    // 上一次单击的时间和坐标，用于判断双击
     curr_songid: u16,               // 当前播放的音频文件，对应列表的第几个，初始化为0
-    curr_songnum: u16,              // 当前的播放列表，含所有推测的音频文件数量，初始化为0
+    jump_input: String,             // 跳转用的编号或时间戳输入缓存，初始化为空
+    seek_steps: [u64; 3],           // 快进快退的步长（毫秒），依次对应无修饰键、Shift、Ctrl
+    status_msg: String,             // 提示信息（如跳转失败、跳过无法播放的歌曲），按下任意键或显示几秒后清除
+    status_time: Instant,           // 提示信息出现的时间
+    skipped: usize,                 // 因无法播放而跳过的歌曲数
+    volume: f32,                    // 音量（0.0 ~ 1.0），初始化为上次退出时的音量
+    muted: bool,                    // 是否静音，静音时volume保留静音前的音量
+    track_cache: TrackInfoCache,    // 按路径缓存的格式信息和总时长
+    tag_cache: HashMap<String, TrackTags>,                      // 按路径缓存的标签信息
+    library: Library,               // 持久化的音乐库索引
+    library_scan: Option<Receiver<LibraryScan>>,                // 正在后台扫描音乐库时的结果通道
+    playlist_hints: HashMap<String, PlaylistEntry>,             // 打开的播放列表文件中记录的标题和时长，在文件本身没有时使用
+    save_input: Option<String>,     // 另存播放列表时输入的文件名，不在输入时为None
+    keymap: KeyMap,                 // 按键到操作的映射，来自配置文件和按键方案
+    help_scroll: Option<u16>,       // 帮助界面滚动到的行，帮助界面关闭时为None
+    themes: Vec<Theme>,             // 内置和配置文件中定义的所有主题
+    theme: usize,                   // 当前使用的主题
 }
 
 // show_song_info: ok!
 //      用来显示歌名、歌曲编号、文件夹音频文件数量，其中第二、三个数据可作为一个控件一起显示。
 // show_song_curr_time, show_song_duration, show_song_progress: ok!
 //      用来显示歌曲的进度，其中第一、二个方法可输出秒数，提供给第三个方法用。
-// time_to_seek: ok!
-//      用来跳转歌曲的指定时间戳。
-// load_folder_path: ok!
-//      用来读取指定文件夹，可输出为一个新的struct，包含指定文件夹内的文件夹(vec)、所有音频文件的名称(vec)。
+// seek_by, seek_to: ok!
+//      用来从当前位置快进快退或跳转到指定时间，实际跳转由播放线程完成。
+// load_file_path: ok!
+//      用来读取指定文件夹，结果存入audio_file_list，包含文件夹内的子文件夹、播放列表和音频文件的名称。
 
 impl App {
-    fn new() -> Self {
-        Self {
+    fn new(args: &Args) -> Result<Self, Box<dyn Error>> {
+        let (config, config_err) = match Config::load(args.config.as_deref()) {
+            Ok(config) => (config, None),
+            Err(err) => (Config::default(), Some(err.to_string())),
+        };
+        let play_mode = args.mode.map(PlayMode::from).unwrap_or(PlayMode::LoopAll);
+        let volume = start_volume(args);
+        let (player, warning) = spawn_player(args, &config, play_mode, volume)?;
+        let overrides = config.keys.into_iter()
+            .map(|(action, keys)| (action, keys.into_vec()))
+            .collect();
+        let (keymap, invalid_keys) = KeyMap::new(config.preset, &overrides);
+        let (themes, theme_errs) = Theme::load_all(&config.themes);
+        // 终端不支持颜色时忽略配置，使用单色主题
+        let theme_name = if Theme::color_supported() {config.theme.as_deref().unwrap_or("default")} else {"mono"};
+        let theme = themes.iter().position(|theme| theme.name == theme_name);
+        let mut status_msg = match config_err {
+            Some(err) => err,
+            None if !invalid_keys.is_empty() => format!("Unknown keys in config: {}", invalid_keys.join(", ")),
+            None if !theme_errs.is_empty() => theme_errs.join("; "),
+            None if theme.is_none() => format!("Unknown theme: {}", theme_name),
+            None => String::new(),
+        };
+        // 索引损坏时从空库开始，原文件已改名保留
+        let library = Library::load().unwrap_or_else(|err| {
+            status_msg = format!("Library index unreadable, kept as .bak: {}", err);
+            Library::default()
+        });
+        if let Some(warning) = warning {
+            status_msg = warning;
+        }
+        Ok(Self {
+            player,
             audio_path: String::new(),
-            audio_sink: Sink::try_new(&OutputStream::try_default().unwrap().1).unwrap(),
             song_name: String::new(),
             song_curr_time: String::new(),
             song_duration: String::new(),
-            song_progress: String::from("-------------------------"),
-            play_state: PlayState::Pause(true),
+            song_progress: Some(0.0),
+            show_remaining: false,
+            status: Status::Stopped,
+            position: Duration::ZERO,
+            play_mode,
             audio_file_list: AudioFileList::new(),
-            curr_folderpath: std::env::current_dir().unwrap(),
-            curr_playlist: vec![String::from("..")],
+            curr_folderpath: std::env::current_dir().unwrap_or_else(|_| PathBuf::from(".")),
+            browser_selected: 0,
+            curr_playlist: vec![],
+            queue_selected: 0,
+            focus: Focus::Browser,
+            browser_offset: 0,
+            queue_offset: 0,
+            ui_areas: UiAreas::default(),
+            last_click: None,
             curr_songid: 0,
-            curr_songnum: 0,
-        }
+            jump_input: String::new(),
+            seek_steps: config.seek_steps.map(|steps| steps.map(|secs| secs * 1000)).unwrap_or([5_000, 30_000, 60_000]),
+            status_msg,
+            status_time: Instant::now(),
+            skipped: 0,
+            volume,
+            muted: false,
+            track_cache: TrackInfoCache::default(),
+            tag_cache: HashMap::new(),
+            library,
+            library_scan: None,
+            playlist_hints: HashMap::new(),
+            save_input: None,
+            keymap,
+            help_scroll: None,
+            themes,
+            theme: theme.unwrap_or(0),
+        })
+    }
+    // 播放队列中的歌曲数
+    fn curr_songnum(&self) -> u16 {
+        self.curr_playlist.len() as u16
     }
     fn show_song_info(&mut self) {
-        self.song_name = self.audio_path.clone();
-    }
-    fn show_song_curr_time(&mut self) -> Result<u64, Box<dyn Error>>{
-        let dur = self.audio_sink.get_pos().as_secs();
-        self.song_curr_time = format!("{}:{:02}:{:02}", dur/3600, dur%3600/60, dur%60);
-        Ok(dur)
-    }
-    fn show_song_duration(&mut self) -> Result<u64, Box<dyn Error>>{
-        if self.audio_path.is_empty() == false {
-            let source = Decoder::new(
-                BufReader::new(File::open(self.audio_path.as_str())?)
-            )?;
-            // let dur = source.total_duration().unwrap().as_secs();
-            let dur = source.total_duration();
-            let dur = match dur {
-                Some(dur) => dur.as_secs(),
-                None => 36000,
+        if self.audio_path.is_empty() {
+            self.song_name.clear();
+            return;
+        }
+        let path = self.audio_path.clone();
+        self.song_tags(&path);
+        self.song_name = self.display_name(&path);
+    }
+    // 显示用的歌名：标签中没有标题时依次使用播放列表文件中的标题、文件名
+    fn display_name(&self, path: &str) -> String {
+        display_name(self.tag_cache.get(path), self.playlist_hints.get(path), path)
+    }
+    // 读取并缓存标签
+    fn song_tags(&mut self, path: &str) -> &TrackTags {
+        self.tag_cache.entry(path.to_string()).or_insert_with(|| TrackTags::read(path))
+    }
+    // 返回当前播放位置（毫秒）；显示剩余时间时，总时长已知则显示为"-剩余时间"
+    fn show_song_curr_time(&mut self) -> u64 {
+        let pos = self.position.as_millis() as u64;
+        let dur = self.track_cache.duration(&self.audio_path);
+        self.song_curr_time = match dur {
+            Some(dur) if self.show_remaining => {
+                format!("-{}", fmt_hms((dur.as_millis() as u64).saturating_sub(pos) / 1000))
+            },
+            _ => fmt_hms(pos / 1000),
+        };
+        pos
+    }
+    // 从缓存读取总时长（毫秒），后台仍在计算时返回None
+    fn show_song_duration(&mut self) -> Option<u64> {
+        if self.audio_path.is_empty() {
+            self.song_duration.clear();
+            return None;
+        }
+        match self.track_cache.duration(&self.audio_path) {
+            Some(dur) => {
+                self.song_duration = fmt_hms(dur.as_secs());
+                Some(dur.as_millis() as u64)
+            },
+            None => {
+                self.song_duration = String::from("-:--:--");
+                None
+            },
+        }
+    }
+    // 进度条的长度随界面宽度变化，这里只记录比例（按毫秒计算），由界面绘制
+    fn show_song_progress(&mut self, curr: u64, dur: Option<u64>) {
+        let dur = match dur {
+            Some(dur) if dur > 0 => dur,
+            _ => {
+                self.song_progress = None;
+                return;
+            },
+        };
+        if self.status != Status::Stopped {
+            self.song_progress = Some((curr as f64 / dur as f64).min(1.0));
+        }
+    }
+    // 调整音量，结果限制在0%到100%之间，调整时自动取消静音
+    fn change_volume(&mut self, delta: f32) {
+        self.volume = ((self.volume + delta) * 100.0).round().clamp(0.0, 100.0) / 100.0;
+        self.muted = false;
+        self.apply_volume();
+    }
+    fn toggle_mute(&mut self) {
+        self.muted = !self.muted;
+        self.apply_volume();
+    }
+    fn apply_volume(&self) {
+        self.player.send(Command::SetVolume(if self.muted {0.0} else {self.volume}));
+    }
+    // 切换到指定编号的歌曲，播放中则继续播放，否则停在暂停状态
+    fn play_songid(&mut self, id: u16) {
+        self.player.send(Command::Jump(id));
+    }
+    fn switch_play_mode(&mut self) {
+        self.player.send(Command::SetMode(self.play_mode.next()));
+    }
+    // 相对当前位置跳转，结果限制在0到总时长之间；解码器不支持跳转时把原因写入提示信息
+    fn seek_by(&mut self, offset: i64) {
+        let curr = self.position.as_millis() as i64;
+        self.seek_to((curr + offset).max(0) as u64)
+    }
+    fn seek_to(&mut self, msec: u64) {
+        if self.status == Status::Stopped {
+            return;
+        }
+        let dur = self.show_song_duration();
+        let msec = match dur {
+            Some(dur) => msec.min(dur),
+            None => msec,
+        };
+        // 解码器不支持跳转时由播放线程发回提示信息
+        self.player.send(Command::Seek(msec));
+        self.position = Duration::from_millis(msec);
+        let curr = self.show_song_curr_time();
+        self.show_song_progress(curr, dur);
+    }
+
+    fn load_file_path(&mut self, path: PathBuf) -> Result<(), Box<dyn Error>> {
+        self.audio_file_list.reset();
+        self.browser_selected = 0;
+        if self.library.contains(&path) {
+            let (dirs, files) = self.library.list_dir(&path);
+            for n in &files {
+                let key = path.join(n).to_string_lossy().into_owned();
+                if let Some(track) = self.library.tracks.get(&key) {
+                    self.tag_cache.insert(key, track.tags.clone());
+                }
+            }
+            self.audio_file_list.dirs = dirs;
+            self.audio_file_list.files = files;
+            // 播放列表文件不在库索引中，仍从文件系统读取
+            let mut skipped = 0;
+            for item in read_dir(&path)?.flatten() {
+                match item.file_name().into_string() {
+                    Ok(n) if is_playlist_file(&n) => self.audio_file_list.insert_playlist(n),
+                    Err(n) if is_playlist_file(&n.to_string_lossy()) => skipped += 1,
+                    _ => {},
+                }
+            }
+            self.audio_file_list.playlists.sort();
+            self.report_unlisted(skipped);
+            return Ok(());
+        }
+        // 名称不是有效的UTF-8的条目无法显示和播放，跳过并在提示信息中报告
+        let mut skipped = 0;
+        for item in read_dir(&path)? {
+            let i = item?;
+            let n = match i.file_name().into_string() {
+                Ok(n) => n,
+                Err(n) => {
+                    let n = n.to_string_lossy();
+                    if i.file_type()?.is_dir() || is_audio_file(&n) || is_playlist_file(&n) {
+                        skipped += 1;
+                    }
+                    continue;
+                },
             };
-            if dur < 36000 {
-                self.song_duration = format!("{}:{:02}:{:02}", dur/3600, dur%3600/60, dur%60);
+            if i.file_type()?.is_dir() {
+                self.audio_file_list.insert_dir(n);
             }
-            else {
-                self.song_duration = "?[-_-#]".to_string();
+            else if i.file_type()?.is_file() && is_audio_file(&n) {
+                self.audio_file_list.insert_file(n);
+            }
+            else if i.file_type()?.is_file() && is_playlist_file(&n) {
+                self.audio_file_list.insert_playlist(n);
+            }
+        }
+        self.audio_file_list.dirs.sort();
+        self.audio_file_list.playlists.sort();
+        self.audio_file_list.files.sort();
+        self.report_unlisted(skipped);
+        Ok(())
+    }
+    fn report_unlisted(&mut self, skipped: usize) {
+        if skipped > 0 {
+            self.notify(format!("{} item(s) with non-UTF-8 names not shown", skipped));
+        }
+    }
+    // 进入文件夹，name为".."时返回上一级，并选中原来所在的文件夹
+    fn enter_folder(&mut self, name: &str) -> Result<(), Box<dyn Error>> {
+        let prev = self.curr_folderpath.clone();
+        let path = if name == ".." {
+            match prev.parent() {
+                Some(parent) => parent.to_path_buf(),
+                None => return Ok(()),
             }
-            Ok(dur)
         }
         else {
-            self.song_duration.clear();
-            Ok(1)
+            prev.join(name)
+        };
+        self.load_file_path(path.clone())?;
+        self.curr_folderpath = path;
+        if name == ".." {
+            if let Some(i) = prev.file_name().and_then(|n| {
+                self.audio_file_list.dirs.iter().position(|d| d.as_str() == n)
+            }) {
+                self.browser_selected = 1 + i;
+            }
+        }
+        Ok(())
+    }
+    // 处理命令行中的路径：全部加入播放队列并立即开始播放，文件浏览器从第一个文件夹（或第一个文件所在的文件夹）开始
+    fn start(&mut self, args: &Args) {
+        let expanded = expand_args(&args.paths, &self.library);
+        for entry in expanded.hints {
+            self.playlist_hints.insert(entry.path.clone(), entry);
+        }
+        if expanded.missing > 0 {
+            self.notify(format!("{} missing file(s)", expanded.missing));
+        }
+        if expanded.skipped > 0 {
+            self.notify(format!("{} file(s) with non-UTF-8 names skipped", expanded.skipped));
+        }
+        if let Some(dir) = expanded.start_dir {
+            self.curr_folderpath = dir;
+        }
+        if !expanded.tracks.is_empty() {
+            // 随机模式下第一首由播放线程随机选取
+            self.player.send(Command::Replace(expanded.tracks, None));
+            if let Some(msec) = args.start {
+                self.player.send(Command::Seek(msec));
+            }
+        }
+        else if !args.paths.is_empty() {
+            self.notify(String::from("No audio files"));
+        }
+        if !expanded.failed.is_empty() {
+            self.notify(format!("Cannot open: {}", expanded.failed.join(", ")));
+        }
+    }
+    // 替换播放列表并立即从第start首开始播放
+    fn play_list(&mut self, list: Vec<String>, start: u16) -> Result<(), Box<dyn Error>> {
+        if list.is_empty() {
+            self.notify(String::from("No audio files"));
+            return Ok(());
+        }
+        self.queue_selected = (start - 1) as usize;
+        self.player.send(Command::Replace(list, Some(start)));
+        Ok(())
+    }
+    // 把歌曲加入播放队列，next为true时插到当前歌曲之后，否则加到末尾；队列原本为空时选定第一首并暂停
+    fn enqueue(&mut self, list: Vec<String>, next: bool) {
+        self.player.send(Command::Enqueue(list, next));
+    }
+    // 移除队列中第i项（从0开始），移除的是当前歌曲时改为播放原位置上的下一首
+    fn queue_remove(&mut self, i: usize) {
+        self.player.send(Command::Remove(i));
+    }
+    // 把队列中第i项上移或下移一位，选中项跟随移动
+    fn queue_move(&mut self, i: usize, up: bool) {
+        let j = match up {
+            true if i > 0 => i - 1,
+            false if i + 1 < self.curr_playlist.len() => i + 1,
+            _ => return,
+        };
+        self.player.send(Command::Move(i, up));
+        self.queue_selected = j;
+    }
+    fn queue_clear(&mut self) {
+        self.player.send(Command::Clear);
+        self.queue_selected = 0;
+    }
+    // 去除队列中的重复项，只保留第一次出现的位置
+    fn queue_dedupe(&mut self) {
+        self.player.send(Command::Dedupe);
+    }
+    // 文件浏览器中所选条目对应的所有歌曲：文件夹为递归的所有音频文件，播放列表为其中的歌曲
+    fn browser_selection(&mut self) -> Result<Vec<String>, Box<dyn Error>> {
+        match self.audio_file_list.entry(self.browser_selected) {
+            BrowserEntry::Parent => Ok(vec![]),
+            BrowserEntry::Dir(name) => self.folder_tracks(&self.curr_folderpath.join(name)),
+            BrowserEntry::Playlist(name) => self.playlist_tracks(&self.curr_folderpath.join(name)),
+            BrowserEntry::File(i) => {
+                let path = self.curr_folderpath.join(&self.audio_file_list.files[i]);
+                Ok(vec![path.to_string_lossy().into_owned()])
+            },
+        }
+    }
+    // 文件夹内的所有歌曲，路径不是有效的UTF-8而被跳过的文件在提示信息中报告
+    fn folder_tracks(&mut self, dir: &Path) -> Result<Vec<String>, Box<dyn Error>> {
+        let (list, skipped) = folder_tracks(&self.library, dir)?;
+        if skipped > 0 {
+            self.notify(format!("{} file(s) with non-UTF-8 names skipped", skipped));
+        }
+        Ok(list)
+    }
+    // 播放列表文件中的所有歌曲，找不到的文件会被跳过并在提示信息中报告
+    fn playlist_tracks(&mut self, path: &Path) -> Result<Vec<String>, Box<dyn Error>> {
+        let (entries, missing) = playlist_tracks(path)?;
+        if missing > 0 {
+            self.notify(format!("{} missing file(s)", missing));
+        }
+        Ok(entries.into_iter()
+            .map(|entry| {
+                let path = entry.path.clone();
+                self.playlist_hints.insert(path.clone(), entry);
+                path
+            })
+            .collect())
+    }
+    fn handle_mouse(&mut self, mouse: MouseEvent) -> Result<(), Box<dyn Error>> {
+        let pos = Position {x: mouse.column, y: mouse.row};
+        let areas = self.ui_areas;
+        match mouse.kind {
+            MouseEventKind::ScrollUp | MouseEventKind::ScrollDown if areas.help.contains(pos) => {
+                self.scroll_help(if mouse.kind == MouseEventKind::ScrollUp {-3} else {3});
+            },
+            MouseEventKind::Down(MouseButton::Left) | MouseEventKind::Drag(MouseButton::Left) if areas.progress.contains(pos) => {
+                if let Some(dur) = self.show_song_duration() {
+                    let ratio = (pos.x - areas.progress.x) as f64 / areas.progress.width.max(1) as f64;
+                    self.seek_to((dur as f64 * ratio) as u64);
+                }
+            },
+            MouseEventKind::ScrollUp if areas.volume.contains(pos) => self.change_volume(0.02),
+            MouseEventKind::ScrollDown if areas.volume.contains(pos) => self.change_volume(-0.02),
+            MouseEventKind::ScrollUp | MouseEventKind::ScrollDown => {
+                let up = mouse.kind == MouseEventKind::ScrollUp;
+                if areas.browser.contains(pos) {
+                    self.browser_selected = if up {self.browser_selected.saturating_sub(1)} else {(self.browser_selected + 1).min(self.audio_file_list.len() - 1)};
+                }
+                else if areas.queue.contains(pos) {
+                    self.queue_selected = if up {self.queue_selected.saturating_sub(1)} else {(self.queue_selected + 1).min(self.curr_playlist.len().saturating_sub(1))};
+                }
+            },
+            MouseEventKind::Down(MouseButton::Left) => {
+                // 同一位置400毫秒内的第二次单击视为双击
+                let double = matches!(self.last_click, Some((time, x, y)) if time.elapsed() < Duration::from_millis(400) && x == pos.x && y == pos.y);
+                self.last_click = if double {None} else {Some((Instant::now(), pos.x, pos.y))};
+                if areas.browser.contains(pos) {
+                    let i = self.browser_offset + (pos.y - areas.browser.y) as usize;
+                    if i < self.audio_file_list.len() {
+                        self.focus = Focus::Browser;
+                        self.browser_selected = i;
+                        if double {
+                            self.browser_open()?;
+                        }
+                    }
+                }
+                else if areas.queue.contains(pos) {
+                    let i = self.queue_offset + (pos.y - areas.queue.y) as usize;
+                    if i < self.curr_playlist.len() {
+                        self.focus = Focus::Queue;
+                        self.queue_selected = i;
+                        if double {
+                            self.player.send(Command::PlayId(i as u16 + 1));
+                        }
+                    }
+                }
+            },
+            _ => {},
         }
+        Ok(())
+    }
+    // 文件浏览器中Enter键的操作：文件夹则进入，文件则从该文件开始播放当前文件夹
+    fn browser_open(&mut self) -> Result<(), Box<dyn Error>> {
+        match self.audio_file_list.entry(self.browser_selected) {
+            BrowserEntry::Parent => self.enter_folder(".."),
+            BrowserEntry::Dir(name) => self.enter_folder(&name),
+            BrowserEntry::Playlist(name) => self.open_playlist(&self.curr_folderpath.join(name)),
+            BrowserEntry::File(i) => {
+                let list = self.folder_files();
+                self.play_list(list, i as u16 + 1)
+            },
+        }
+    }
+    // 播放所选条目：文件夹则递归播放其中所有音频文件，文件则只播放这一首
+    fn browser_play(&mut self, recursive: bool) -> Result<(), Box<dyn Error>> {
+        match self.audio_file_list.entry(self.browser_selected) {
+            BrowserEntry::Dir(name) if recursive => {
+                let list = self.folder_tracks(&self.curr_folderpath.join(name))?;
+                self.play_list(list, 1)
+            },
+            BrowserEntry::Playlist(name) => self.open_playlist(&self.curr_folderpath.join(name)),
+            BrowserEntry::File(i) => {
+                let path = self.curr_folderpath.join(&self.audio_file_list.files[i]);
+                self.play_list(vec![path.to_string_lossy().into_owned()], 1)
+            },
+            _ => Ok(()),
+        }
+    }
+    // 打开播放列表文件作为当前播放列表
+    fn open_playlist(&mut self, path: &Path) -> Result<(), Box<dyn Error>> {
+        let list = self.playlist_tracks(path)?;
+        let msg = self.status_msg.clone();
+        self.play_list(list, 1)?;
+        if !msg.is_empty() {
+            self.notify(msg);
+        }
+        Ok(())
+    }
+    // 把当前播放列表保存到文件浏览器所在的文件夹，格式由扩展名决定
+    fn save_curr_playlist(&mut self, name: &str) -> Result<(), Box<dyn Error>> {
+        let path = self.curr_folderpath.join(name);
+        if PlaylistFormat::from_path(&path).is_none() {
+            self.notify(String::from("Use .m3u8/.pls/.xspf"));
+            return Ok(());
+        }
+        let entries = self.curr_playlist.clone().iter()
+            .map(|p| {
+                let hint = self.playlist_hints.get(p).cloned();
+                let tags = self.song_tags(p).clone();
+                PlaylistEntry {
+                    path: p.clone(),
+                    title: match (&tags.title, hint.as_ref().and_then(|h| h.title.clone())) {
+                        (None, Some(title)) => Some(title),
+                        (None, None) => None,
+                        _ => Some(tags.display_name(p)),
+                    },
+                    duration: self.track_cache.duration(p)
+                        .or_else(|| self.library.tracks.get(p).and_then(|t| t.duration_ms).map(Duration::from_millis))
+                        .or(hint.and_then(|h| h.duration)),
+                }
+            })
+            .collect::<Vec<_>>();
+        save_playlist(&path, &entries)?;
+        self.load_file_path(self.curr_folderpath.clone())?;
+        self.notify(format!("Saved {}", name));
+        Ok(())
+    }
+    // 把当前文件夹添加为音乐库的根文件夹并扫描，扫描完成前浏览器仍按原来的音乐库显示
+    fn add_library_root(&mut self) -> Result<(), Box<dyn Error>> {
+        if self.library_scan.is_some() {
+            self.notify(String::from("Library scan in progress"));
+            return Ok(());
+        }
+        let mut library = self.library.clone();
+        if !library.add_root(&self.curr_folderpath)? {
+            self.notify(String::from("Already in library"));
+            return Ok(());
+        }
+        self.scan_library(library);
+        Ok(())
     }
-    fn show_song_progress(&mut self, curr: u64, dur: u64) {
-        if dur == 36000 {
-            self.song_progress = "============/============".to_string();
+    fn rescan_library(&mut self) {
+        if self.library_scan.is_some() {
+            self.notify(String::from("Library scan in progress"));
             return;
         }
-        let length = 25;
-        let progress = curr * length / dur;
-        if !self.audio_sink.empty() {
-            self.song_progress.clear();
-            for _ in 0..progress {self.song_progress.push('=');}
-            self.song_progress.push('>');
-            for _ in 0..(length-progress) {self.song_progress.push('-');}
+        self.scan_library(self.library.clone());
+    }
+    // 在后台线程中增量扫描音乐库并保存索引，大的音乐库也不会卡住界面
+    fn scan_library(&mut self, mut library: Library) {
+        let (tx, rx) = channel();
+        thread::spawn(move || {
+            let stats = library.rescan();
+            let saved = library.save();
+            let _ = tx.send((library, stats, saved));
+        });
+        self.library_scan = Some(rx);
+        self.notify(String::from("Scanning library..."));
+    }
+    // 接收后台扫描的结果，换用新的音乐库并刷新文件浏览器
+    fn update_library_scan(&mut self) {
+        let result = match self.library_scan.as_ref().map(Receiver::try_recv) {
+            None | Some(Err(TryRecvError::Empty)) => return,
+            Some(result) => result,
+        };
+        self.library_scan = None;
+        let (library, stats, saved) = match result {
+            Ok(scan) => scan,
+            Err(_) => return self.notify(String::from("Library scan failed")),
+        };
+        self.library = library;
+        for (path, track) in &self.library.tracks {
+            self.tag_cache.insert(path.clone(), track.tags.clone());
+        }
+        let selected = self.browser_selected;
+        if let Err(err) = self.load_file_path(self.curr_folderpath.clone()) {
+            self.notify(err.to_string());
+            return;
+        }
+        self.browser_selected = selected.min(self.audio_file_list.len() - 1);
+        let skipped = match stats.skipped {
+            0 => String::new(),
+            n => format!(", {} with non-UTF-8 names skipped", n),
+        };
+        match saved {
+            Ok(()) => self.notify(format!("+{} ~{} -{} tracks{}", stats.added, stats.updated, stats.removed, skipped)),
+            Err(err) => self.notify(format!("+{} ~{} -{} tracks{}, not saved: {}", stats.added, stats.updated, stats.removed, skipped, err)),
+        }
+    }
+    fn theme(&self) -> &Theme {
+        &self.themes[self.theme]
+    }
+    // 滚动帮助界面，超出范围的部分在绘制时修正
+    fn scroll_help(&mut self, lines: i32) {
+        if let Some(scroll) = self.help_scroll.as_mut() {
+            *scroll = (*scroll as i32 + lines).clamp(0, u16::MAX as i32) as u16;
         }
     }
-    fn time_to_seek(&mut self, msec: u64) -> Result<(), Box<dyn Error>> {
-        self.audio_sink.try_seek(Duration::from_millis(msec))?;
+    // 根据播放线程的事件更新状态副本和显示内容
+    fn handle_event(&mut self, event: &PlayerEvent) {
+        match event {
+            PlayerEvent::State(state) => {
+                let track_changed = state.path != self.audio_path;
+                self.status = state.status;
+                self.curr_songid = state.current;
+                self.audio_path.clone_from(&state.path);
+                self.play_mode = state.mode;
+                self.position = state.position;
+                if track_changed {
+                    self.show_song_info();
+                }
+            },
+            PlayerEvent::Queue(list) => {
+                self.curr_playlist.clone_from(list);
+                self.queue_selected = self.queue_selected.min(list.len().saturating_sub(1));
+            },
+            PlayerEvent::Loaded {path, duration, channels, sample_rate, ..} => {
+                let hint = self.playlist_hints.get(path).and_then(|entry| entry.duration);
+                self.track_cache.insert(path, *duration, hint, *channels, *sample_rate);
+            },
+            PlayerEvent::Skipped {path, error, ..} => {
+                self.skipped += 1;
+                // 路径已由歌名表示，只显示原因
+                let reason = error.source().map(|e| e.to_string()).unwrap_or_else(|| error.to_string());
+                self.notify(format!("Skipped {}: {}", self.display_name(path), reason));
+            },
+            PlayerEvent::Error(error) => self.notify(error.to_string()),
+            PlayerEvent::Message(msg) => self.notify(msg.clone()),
+            // 顺序播放一次播完时已回到第一首并停止，进度条归零
+            PlayerEvent::Finished => {
+                self.song_progress = Some(0.0);
+                self.notify(String::from("Finished"));
+            },
+        }
+    }
+    // 处理播放线程发来的所有事件并刷新进度显示，播放线程已退出（如崩溃）时返回错误
+    fn process_events(&mut self) -> Result<(), Box<dyn Error>> {
+        loop {
+            match self.player.events.try_recv() {
+                Ok(event) => self.handle_event(&event),
+                Err(TryRecvError::Empty) => break,
+                Err(TryRecvError::Disconnected) => return Err("player stopped".into()),
+            }
+        }
+        self.track_cache.update();
+        self.update_library_scan();
+        if self.status_time.elapsed() >= STATUS_TIMEOUT {
+            self.status_msg.clear();
+        }
+        let curr = self.show_song_curr_time();
+        let dur = self.show_song_duration();
+        self.show_song_progress(curr, dur);
+        if self.audio_path.is_empty() {
+            self.song_curr_time.clear();
+        }
         Ok(())
     }
-    fn load_file_path(&mut self, path: PathBuf) -> Result<(), Box<dyn Error>> {
-        self.audio_file_list.reset();
-        for item in read_dir(path)? {
-            let i = item?;
-            let n = i.file_name().into_string().unwrap();
-            if i.file_type()?.is_file() && n.ends_with("mp3") || n.ends_with("flac") || n.ends_with("ogg") || n.ends_with("wav") {
-                self.audio_file_list.insert_file(n);
+    // 在提示行显示一条信息，一段时间后自动消失
+    fn notify(&mut self, msg: impl Into<String>) {
+        self.status_msg = msg.into();
+        self.status_time = Instant::now();
+    }
+    // 确认跳转输入：含":"时为时间戳，否则为播放队列中的编号
+    fn confirm_jump(&mut self) {
+        if self.jump_input.contains(':') {
+            match parse_timestamp(&self.jump_input) {
+                Some(msec) => self.seek_to(msec),
+                None => self.notify(String::from("Invalid timestamp")),
             }
         }
+        else if let Ok(id) = self.jump_input.parse::<u16>() {
+            self.play_songid(id);
+        }
+        self.jump_input.clear();
+    }
+    // 执行按键对应的操作；退出由run_app处理
+    fn run_action(&mut self, action: Action) -> Result<(), Box<dyn Error>> {
+        match action {
+            Action::LoadFolder => {
+                let _ = self.load_file_path(self.curr_folderpath.clone());
+                if self.audio_file_list.files.is_empty() {
+                    self.notify(String::from("there's no audio files."));
+                }
+                else {
+                    let list = self.folder_files();
+                    self.enqueue(list, false);
+                }
+            },
+            Action::SwitchFocus => {
+                self.focus = if self.focus == Focus::Browser {Focus::Queue} else {Focus::Browser};
+            },
+            Action::MoveUp | Action::MoveDown if self.focus == Focus::Queue => {
+                self.queue_move(self.queue_selected, action == Action::MoveUp);
+            },
+            Action::Up if self.focus == Focus::Queue => {
+                self.queue_selected = self.queue_selected.saturating_sub(1);
+            },
+            Action::Down if self.focus == Focus::Queue => {
+                self.queue_selected = (self.queue_selected + 1).min(self.curr_playlist.len().saturating_sub(1));
+            },
+            Action::Up => {
+                self.browser_selected = self.browser_selected.saturating_sub(1);
+            },
+            Action::Down if self.browser_selected + 1 < self.audio_file_list.len() => {
+                self.browser_selected += 1;
+            },
+            Action::Remove if self.focus == Focus::Queue => self.queue_remove(self.queue_selected),
+            Action::Dedupe => self.queue_dedupe(),
+            Action::ClearQueue => self.queue_clear(),
+            Action::Enqueue | Action::EnqueueNext if self.focus == Focus::Browser => {
+                let list = self.browser_selection()?;
+                self.enqueue(list, action == Action::EnqueueNext);
+            },
+            Action::PlayFolder => self.browser_play(true)?,
+            Action::PlayFile => self.browser_play(false)?,
+            Action::AddLibraryRoot => self.add_library_root()?,
+            Action::RescanLibrary => self.rescan_library(),
+            Action::SavePlaylist if self.curr_songnum() != 0 => {
+                self.save_input = Some(String::from("playlist.m3u8"));
+            },
+            Action::VolumeUp => self.change_volume(0.02),
+            Action::VolumeDown => self.change_volume(-0.02),
+            Action::VolumeUpCoarse => self.change_volume(0.1),
+            Action::VolumeDownCoarse => self.change_volume(-0.1),
+            Action::ToggleRemaining => {
+                self.show_remaining = !self.show_remaining;
+                self.show_song_curr_time();
+            },
+            Action::ToggleMute => self.toggle_mute(),
+            Action::CycleMode => self.switch_play_mode(),
+            Action::NextTrack => self.player.send(Command::Next),
+            // 播放超过3秒时先回到本首开头
+            Action::PrevTrack => self.player.send(Command::Prev),
+            Action::Restart => self.player.send(Command::Restart),
+            Action::PlayPause => self.player.send(Command::TogglePause),
+            Action::SeekForward => self.seek_by(self.seek_steps[0] as i64),
+            Action::SeekBackward => self.seek_by(-(self.seek_steps[0] as i64)),
+            Action::SeekForwardMedium => self.seek_by(self.seek_steps[1] as i64),
+            Action::SeekBackwardMedium => self.seek_by(-(self.seek_steps[1] as i64)),
+            Action::SeekForwardLarge => self.seek_by(self.seek_steps[2] as i64),
+            Action::SeekBackwardLarge => self.seek_by(-(self.seek_steps[2] as i64)),
+            Action::Parent => self.enter_folder("..")?,
+            Action::Open if self.focus == Focus::Queue && !self.curr_playlist.is_empty() => {
+                self.player.send(Command::PlayId(self.queue_selected as u16 + 1));
+            },
+            // 队列为空时没有可播放的条目
+            Action::Open if self.focus == Focus::Queue => {},
+            Action::Open => self.browser_open()?,
+            Action::Help => self.help_scroll = Some(0),
+            Action::CycleTheme => {
+                self.theme = (self.theme + 1) % self.themes.len();
+                self.notify(format!("Theme: {}", self.theme().name));
+            },
+            _ => {}
+        }
         Ok(())
     }
+    // 当前文件夹内所有音频文件的完整路径
+    fn folder_files(&self) -> Vec<String> {
+        self.audio_file_list.files.iter()
+            .map(|name| self.curr_folderpath.join(name).to_string_lossy().into_owned())
+            .collect()
+    }
 }
 
-fn ui(f: &mut Frame, app: &App) {
-    f.render_widget(
-        Block::bordered(),
-        Rect {x: 0, y: 0, width: 45, height: 6}
-    );  // 主界面
+// 显示用的歌名：标签中没有标题时依次使用播放列表文件中的标题、文件名
+fn display_name(tags: Option<&TrackTags>, hint: Option<&PlaylistEntry>, path: &str) -> String {
+    match (tags.and_then(|tags| tags.title.as_ref()), hint.and_then(|entry| entry.title.clone())) {
+        (None, Some(title)) => title,
+        _ => tags.cloned().unwrap_or_default().display_name(path),
+    }
+}
 
-    f.render_widget(
-        Paragraph::new("(P)Play/Pause (Q)Quit"),
-        Rect {x: 2, y: 4, width: 41, height: 1}
-    );  // 操作简易说明
+// 把形如1:23:45、23:45的时间戳解析为毫秒
+fn parse_timestamp(text: &str) -> Option<u64> {
+    let mut secs = 0u64;
+    for part in text.split(':') {
+        if part.is_empty() {
+            return None;
+        }
+        secs = secs.checked_mul(60)?.checked_add(part.parse().ok()?)?;
+    }
+    Some(secs * 1000)
+}
 
-    f.render_widget(
-        Paragraph::new(app.song_name.clone()).centered(),
-        Rect {x: 2, y: 1,width: 41, height: 1}
-    );  // 显示歌名
+fn fmt_hms(secs: u64) -> String {
+    format!("{}:{:02}:{:02}", secs/3600, secs%3600/60, secs%60)
+}
 
-    f.render_widget(
-        Paragraph::new(app.song_curr_time.clone()),
-        Rect {
-            x: 2,
-            y: 3,
-            width: app.song_curr_time.len() as u16,
-            height: 1
+fn ui(f: &mut Frame, app: &mut App) {
+    let area = f.size();
+    app.ui_areas = UiAreas::default();
+    f.render_widget(Block::new().style(app.theme().base()), area);  // 主题的背景色和文字颜色
+    // 终端太小时只显示迷你播放器
+    if area.height < 8 || area.width < 40 {
+        ui_mini_player(f, app, area);
+        if app.help_scroll.is_some() {
+            ui_help(f, app, area);
         }
-    );  // 显示歌曲当前时间戳
+        return;
+    }
+    let [player, rest] = Layout::vertical([Constraint::Length(6), Constraint::Min(0)]).areas(area);
+    ui_player(f, app, player);
+    if rest.height < 3 {
+        return;
+    }
+    // 终端足够宽时文件浏览器和播放队列左右并排，否则上下排列
+    let [browser, queue] = if rest.width >= 80 {
+        Layout::horizontal([Constraint::Percentage(50), Constraint::Percentage(50)]).areas(rest)
+    }
+    else if rest.height >= 8 {
+        Layout::vertical([Constraint::Percentage(50), Constraint::Percentage(50)]).areas(rest)
+    }
+    else {
+        [rest, Rect::default()]
+    };
+    ui_browser(f, app, browser);
+    if queue.height >= 3 {
+        ui_queue(f, app, queue);
+    }
+    if app.help_scroll.is_some() {
+        ui_help(f, app, area);
+    }
+}
 
-    f.render_widget(
-        Paragraph::new(app.song_duration.clone()),
-        Rect {
-            x: 43 - app.song_duration.len() as u16,
-            y: 3,
-            width: app.song_duration.len() as u16,
-            height: 1
+// 帮助界面：按分类列出所有操作和当前绑定的按键，覆盖在整个界面之上
+fn ui_help(f: &mut Frame, app: &mut App, area: Rect) {
+    let area = area.inner(Margin {horizontal: 2, vertical: 1});
+    let theme = app.theme().clone();
+    let block = theme.block(true)
+        .title(" Help ")
+        .title_bottom(Line::from(" ↑↓/PgUp/PgDn scroll · Esc close ").right_aligned());
+    let inner = block.inner(area);
+    let mut lines = vec![];
+    for (category, actions) in keymap::CATEGORIES {
+        if !lines.is_empty() {
+            lines.push(Line::from(""));
+        }
+        lines.push(Line::styled(category, theme.accent()));
+        for &action in actions {
+            let keys = app.keymap.keys(action).iter().map(|key| key.display()).collect::<Vec<_>>();
+            let keys = if keys.is_empty() {String::from("-")} else {keys.join(", ")};
+            lines.push(Line::from(format!("  {:<24}{}", keys, action.label())));
         }
-    );  // 显示歌曲当前总时长
+    }
+    // 跳转编号和时间戳的输入不在按键映射中，单独列出
+    lines.push(Line::from(""));
+    lines.push(Line::styled("Jump", theme.accent()));
+    lines.push(Line::from(format!("  {:<24}{}", "0-9, Enter", "Play track number")));
+    lines.push(Line::from(format!("  {:<24}{}", "h:mm:ss, Enter", "Seek to timestamp")));
+    let max_scroll = (lines.len() as u16).saturating_sub(inner.height);
+    let scroll = app.help_scroll.unwrap_or(0).min(max_scroll);
+    app.help_scroll = Some(scroll);
+    // 帮助界面遮住的控件不再响应鼠标
+    app.ui_areas = UiAreas {help: inner, ..UiAreas::default()};
+    f.render_widget(Clear, area);
+    f.render_widget(Paragraph::new(lines).block(block).scroll((scroll, 0)), area);
+}
 
-    f.render_widget(
-        Paragraph::new(app.song_progress.clone()),
-        Rect {x: 10, y: 3, width: 25, height: 1}
-    );  // 显示歌曲进度
+fn volume_text(app: &App) -> String {
+    if app.muted {
+        String::from(" Mute ")
+    }
+    else {
+        let level = (app.volume * 10.0).round() as usize;
+        let [on, off] = app.theme().volume_glyphs;
+        format!(" {}{} {:>3}% ", on.to_string().repeat(level), off.to_string().repeat(10 - level), (app.volume * 100.0).round())
+    }
+}
 
-    f.render_widget(
-        Paragraph::new(format!("----kbps {:03}/{:03}", app.curr_songid, app.curr_songnum)),
-        Rect {x: 27, y: 4, width: 16, height: 1}
-    );  // 显示码率和播放情况
+fn bitrate_text(app: &App) -> String {
+    match app.track_cache.get(&app.audio_path).and_then(|info| info.bitrate) {
+        Some(kbps) => format!("{:>4}kbps", kbps.min(9999)),
+        None => String::from("----kbps"),
+    }
+}
 
+// 提示行：输入中的内容、提示信息或操作简易说明
+fn hint_text(app: &App) -> String {
+    if let Some(name) = &app.save_input {
+        format!("Save as: {}_", name)
+    }
+    else if !app.jump_input.is_empty() {
+        format!("Go to: {}_", app.jump_input)
+    }
+    else if !app.status_msg.is_empty() {
+        app.status_msg.clone()
+    }
+    else {
+        [(Action::PlayPause, "Play"), (Action::NextTrack, "Next"), (Action::PrevTrack, "Prev"), (Action::Help, "Help"), (Action::Quit, "Quit")]
+            .iter()
+            .filter(|(action, _)| !app.keymap.keys(*action).is_empty())
+            .map(|(action, name)| format!("({}){}", app.keymap.key_name(*action), name))
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+}
+
+// 一行时间和进度条：当前时间 进度条 总时长
+// 返回进度条所在的区域
+fn ui_progress_line(f: &mut Frame, app: &App, area: Rect) -> Rect {
+    let [curr, _, bar, _, dur] = Layout::horizontal([
+        Constraint::Length(app.song_curr_time.chars().count() as u16),
+        Constraint::Length(1),
+        Constraint::Min(0),
+        Constraint::Length(1),
+        Constraint::Length(app.song_duration.chars().count() as u16),
+    ]).areas(area);
+    f.render_widget(Paragraph::new(app.song_curr_time.clone()), curr);
+    let theme = app.theme();
+    match app.song_progress {
+        // 主题指定了字符时逐格绘制，当前位置可以用单独的字符标出
+        Some(ratio) if !theme.progress_glyphs.is_empty() => {
+            let glyphs = &theme.progress_glyphs;
+            let width = bar.width as usize;
+            let filled = ((width as f64 * ratio).round() as usize).min(width);
+            let mut spans = vec![Span::styled(glyphs[0].to_string().repeat(filled), Style::new().fg(theme.accent))];
+            let mut empty = width - filled;
+            if glyphs.len() == 3 && empty > 0 {
+                spans.push(Span::styled(glyphs[1].to_string(), Style::new().fg(theme.accent)));
+                empty -= 1;
+            }
+            spans.push(Span::styled(glyphs[glyphs.len() - 1].to_string().repeat(empty), theme.dim()));
+            f.render_widget(Paragraph::new(Line::from(spans)), bar);
+        },
+        // 用部分方块字符绘制，任意宽度下都能平滑前进；不用Gauge，它会把正中一格留给标签
+        Some(ratio) => {
+            let width = bar.width as usize;
+            let filled = width as f64 * ratio.clamp(0.0, 1.0);
+            let full = (filled.floor() as usize).min(width);
+            let mut text = symbols::block::FULL.repeat(full);
+            if full < width {
+                text.push_str(partial_block(filled.fract()));
+                text.push_str(&" ".repeat(width - full - 1));
+            }
+            f.render_widget(Paragraph::new(text).style(Style::new().fg(theme.accent).bg(theme.dim)), bar);
+        },
+        // 总时长未知时显示为中间带"/"的虚线
+        None => {
+            let half = (bar.width.saturating_sub(1) / 2) as usize;
+            let rest = (bar.width as usize).saturating_sub(half + 1);
+            f.render_widget(Paragraph::new(format!("{}/{}", "╌".repeat(half), "╌".repeat(rest))).style(theme.dim()), bar);
+        },
+    }
+    f.render_widget(Paragraph::new(app.song_duration.clone()), dur);
+    bar
+}
+
+// 进度条末端不满一格的部分，按八分之一格取整
+fn partial_block(frac: f64) -> &'static str {
+    match (frac * 8.0).round() as u8 {
+        0 => " ",
+        1 => symbols::block::ONE_EIGHTH,
+        2 => symbols::block::ONE_QUARTER,
+        3 => symbols::block::THREE_EIGHTHS,
+        4 => symbols::block::HALF,
+        5 => symbols::block::FIVE_EIGHTHS,
+        6 => symbols::block::THREE_QUARTERS,
+        7 => symbols::block::SEVEN_EIGHTHS,
+        _ => symbols::block::FULL,
+    }
+}
+
+fn ui_player(f: &mut Frame, app: &mut App, area: Rect) {
+    let volume = volume_text(app);
+    let volume_width = volume.chars().count() as u16;
+    app.ui_areas.volume = Rect {
+        x: area.right().saturating_sub(volume_width + 1),
+        y: area.bottom().saturating_sub(1),
+        width: volume_width,
+        height: 1,
+    };
+    let block = app.theme().block(false)
+        .title_style(app.theme().accent())
+        .title(app.tag_cache.get(&app.audio_path).map(|tags| tags.album_desc()).unwrap_or_default())
+        .title_bottom(Line::from(volume).right_aligned());
+    let inner = block.inner(area).inner(Margin::new(1, 0));
+    f.render_widget(block, area);  // 主界面，标题栏显示专辑信息，底边显示音量
+
+    let [name, mode, progress, hint] = Layout::vertical([Constraint::Length(1); 4]).areas(inner);
+    f.render_widget(Paragraph::new(app.song_name.clone()).style(app.theme().accent()).centered(), name);  // 显示歌名
+
+    // Paragraph::new("⇒ ↻ ① ✈ A → B"),
+    let skipped = if app.skipped > 0 {format!("  {} skipped", app.skipped)} else {String::new()};
+    f.render_widget(Paragraph::new(format!("{} ---{}", app.play_mode.icons(), skipped)), mode);  // 显示播放模式（部分为UTF-8图标）和跳过的歌曲数
     f.render_widget(
-        // Paragraph::new("⇒ ↻ ① ✈ A → B"),
-        Paragraph::new("- L - - ---"),
-        Rect {x: 2, y: 2, width: 41, height: 1}
-    );  // 显示播放模式（部分为UTF-8图标）
+        Paragraph::new(app.track_cache.get(&app.audio_path).map(|info| info.format_desc()).unwrap_or_default()).right_aligned(),
+        mode
+    );  // 显示采样率、位深和声道数
+
+    app.ui_areas.progress = ui_progress_line(f, app, progress);  // 显示歌曲当前时间戳、进度、总时长
+
+    let counter = format!("{} {:03}/{:03}", bitrate_text(app), app.curr_songid, app.curr_songnum());
+    let [hint, counter_area] = Layout::horizontal([
+        Constraint::Min(0),
+        Constraint::Length(counter.len() as u16 + 1),
+    ]).areas(hint);
+    f.render_widget(Paragraph::new(hint_text(app)), hint);  // 操作简易说明，输入跳转内容或有提示信息时改为显示它们
+    f.render_widget(Paragraph::new(counter).right_aligned(), counter_area);  // 显示码率和播放情况
 }
 
-fn run_app<B: Backend>(terminal: &mut Terminal<B>, mut app: App) -> Result<(), Box<dyn Error>> {
-    let (tx, rx) = channel();
-    let (_stream, stream_handle) = OutputStream::try_default()?;
-    app.audio_sink = Sink::try_new(&stream_handle)?;
+// 迷你播放器：不画边框，按可用行数依次显示歌名、进度、播放模式和音量
+fn ui_mini_player(f: &mut Frame, app: &mut App, area: Rect) {
+    let rows = Layout::vertical([Constraint::Length(1); 3]).split(area);
+    if area.height >= 2 {
+        f.render_widget(Paragraph::new(app.song_name.clone()).style(app.theme().accent()), rows[0]);
+        app.ui_areas.progress = ui_progress_line(f, app, rows[1]);
+    }
+    else {
+        app.ui_areas.progress = ui_progress_line(f, app, rows[0]);
+    }
+    if area.height >= 3 {
+        f.render_widget(
+            Paragraph::new(format!("{} {:03}/{:03}", app.play_mode.icons(), app.curr_songid, app.curr_songnum())),
+            rows[2]
+        );
+        let volume = volume_text(app);
+        let width = volume.chars().count() as u16;
+        app.ui_areas.volume = Rect {x: rows[2].right().saturating_sub(width), width: width.min(rows[2].width), ..rows[2]};
+        f.render_widget(Paragraph::new(volume).right_aligned(), rows[2]);
+    }
+}
+
+fn focus_style(app: &App, focus: Focus) -> Style {
+    app.theme().selection(app.focus == focus)
+}
+
+fn ui_browser(f: &mut Frame, app: &mut App, area: Rect) {
+    let block = app.theme().block(app.focus == Focus::Browser).title(app.curr_folderpath.to_string_lossy().into_owned());
+    app.ui_areas.browser = block.inner(area);
+    // 只为这次能显示出来的行读取标签，进入有几百首歌的文件夹时不必等全部读完
+    let rows = visible_rows(app.browser_offset, app.browser_selected, app.ui_areas.browser.height, app.audio_file_list.len());
+    let skip = 1 + app.audio_file_list.dirs.len() + app.audio_file_list.playlists.len();
+    for row in rows.start.max(skip)..rows.end {
+        let path = app.curr_folderpath.join(&app.audio_file_list.files[row - skip]).to_string_lossy().into_owned();
+        app.song_tags(&path);
+    }
+    let mut items = vec![String::from("..")];
+    items.extend(app.audio_file_list.dirs.iter().map(|d| format!("{}/", d)));
+    items.extend(app.audio_file_list.playlists.iter().map(|p| format!("[{}]", p)));
+    items.extend(app.audio_file_list.files.iter().map(|n| {
+        let path = app.curr_folderpath.join(n).to_string_lossy().into_owned();
+        match app.tag_cache.get(&path) {
+            Some(tags) => tags.display_name(&path),
+            None => n.clone(),
+        }
+    }));
+    let mut state = ListState::default().with_offset(app.browser_offset).with_selected(Some(app.browser_selected));
+    f.render_stateful_widget(
+        List::new(items)
+            .block(block)
+            .highlight_style(focus_style(app, Focus::Browser)),
+        area,
+        &mut state
+    );  // 文件浏览器
+    app.browser_offset = state.offset();
+}
+
+// 列表这次显示的行，和List的滚动方式一致：保持上次的位置，但要让选中的行可见
+fn visible_rows(offset: usize, selected: usize, height: u16, len: usize) -> Range<usize> {
+    let first = offset.min(selected).max((selected + 1).saturating_sub(height as usize));
+    first..(first + height as usize).min(len)
+}
+
+fn ui_queue(f: &mut Frame, app: &mut App, area: Rect) {
+    let block = app.theme().block(app.focus == Focus::Queue).title(format!("Queue ({})", app.curr_playlist.len()));
+    app.ui_areas.queue = block.inner(area);
+    for row in visible_rows(app.queue_offset, app.queue_selected, app.ui_areas.queue.height, app.curr_playlist.len()) {
+        let path = app.curr_playlist[row].clone();
+        app.song_tags(&path);
+    }
+    let items = app.curr_playlist.iter().enumerate().map(|(i, path)| {
+        if i as u16 + 1 == app.curr_songid {
+            ListItem::new(format!("▶ {}", app.display_name(path))).style(app.theme().accent())
+        }
+        else {
+            ListItem::new(format!("  {}", app.display_name(path)))
+        }
+    }).collect::<Vec<_>>();
+    let mut state = ListState::default()
+        .with_offset(app.queue_offset)
+        .with_selected(Some(app.queue_selected).filter(|_| !app.curr_playlist.is_empty()));
+    f.render_stateful_widget(
+        List::new(items)
+            .block(block)
+            .highlight_style(focus_style(app, Focus::Queue)),
+        area,
+        &mut state
+    );  // 播放队列，当前歌曲加粗并标记▶
+    app.queue_offset = state.offset();
+}
+
+fn run_app<B: Backend>(terminal: &mut Terminal<B>, mut app: App, args: &Args) -> Result<(), Box<dyn Error>> {
+    app.start(args);
+    if let Err(err) = app.load_file_path(app.curr_folderpath.clone()) {
+        app.notify(err.to_string());
+    }
     loop {
-        terminal.draw(|f| ui(f, &app))?;
+        terminal.draw(|f| ui(f, &mut app))?;
         if event::poll(Duration::from_millis(16))? {
-            if let Event::Key(key) = event::read()? {
-                match key.code {
-                    KeyCode::Char('q') => {
-                        tx.send(())?;
-                        return Ok(());
-                    },
-                    KeyCode::Char('p') if app.curr_songid != 0 => {
-                        match app.play_state {
-                            PlayState::Pause(false) | PlayState::Play(true) if key.kind == KeyEventKind::Press => {
-                                app.audio_sink.play();
-                                if rx.try_recv().is_ok() {
-                                    app.audio_sink.sleep_until_end();
-                                    app.audio_sink.clear();
-                                }
-                                app.play_state = PlayState::Play(false);
+            let event = event::read()?;
+            // 终端大小变化时清屏，下一次绘制会按新尺寸重新布局
+            if let Event::Resize(_, _) = event {
+                terminal.clear()?;
+            }
+            if let Event::Mouse(mouse) = event {
+                if let Err(err) = app.handle_mouse(mouse) {
+                    app.notify(err.to_string());
+                }
+            }
+            if let Event::Key(key) = event {
+                if key.kind == KeyEventKind::Press {
+                    app.status_msg.clear();
+                }
+                // 正在输入另存的文件名时，按键只用于编辑文件名
+                if let Some(name) = app.save_input.as_mut() {
+                    if key.kind == KeyEventKind::Press {
+                        match key.code {
+                            KeyCode::Char(c) => name.push(c),
+                            KeyCode::Backspace => {
+                                name.pop();
                             },
-                            PlayState::Play(false) | PlayState::Pause(true) if key.kind == KeyEventKind::Press => {
-                                app.audio_sink.pause();
-                                app.play_state = PlayState::Pause(false);
+                            KeyCode::Esc => app.save_input = None,
+                            KeyCode::Enter => {
+                                let name = name.clone();
+                                app.save_input = None;
+                                if let Err(err) = app.save_curr_playlist(&name) {
+                                    app.notify(err.to_string());
+                                }
                             },
                             _ => {}
                         }
-                        
-                    },
-                    KeyCode::Char('l') => {
-                        let _ = app.load_file_path(app.curr_folderpath.clone());
-                        app.curr_songnum = app.audio_file_list.files.len() as u16;
-                        if app.curr_songnum != 0 {
-                            app.curr_songid = 1;
-                            app.curr_playlist = app.audio_file_list.files.clone();
-                            app.audio_path = app.curr_playlist[(app.curr_songid - 1) as usize].clone();
-                        }
-                        else {
-                            app.curr_songid = 0;
-                            app.song_name = String::from("there's no audio files.")
+                    }
+                    continue;
+                }
+                // 帮助界面打开时，按键只用于滚动和关闭帮助，退出键仍然有效
+                if app.help_scroll.is_some() {
+                    let action = app.keymap.action(&key);
+                    if key.kind != KeyEventKind::Release && action != Some(Action::Quit) {
+                        let page = app.ui_areas.help.height.max(1) as i32;
+                        match (key.code, action) {
+                            (KeyCode::Esc, _) | (_, Some(Action::Help)) if key.kind == KeyEventKind::Press => app.help_scroll = None,
+                            (KeyCode::Up, _) | (_, Some(Action::Up)) => app.scroll_help(-1),
+                            (KeyCode::Down, _) | (_, Some(Action::Down)) => app.scroll_help(1),
+                            (KeyCode::PageUp, _) => app.scroll_help(-page),
+                            (KeyCode::PageDown | KeyCode::Char(' '), _) => app.scroll_help(page),
+                            (KeyCode::Home, _) => app.help_scroll = Some(0),
+                            (KeyCode::End, _) => app.scroll_help(u16::MAX as i32),
+                            _ => {}
                         }
+                        continue;
                     }
-                    _ => {}
                 }
-            }
-        }
-        else {
-            if app.audio_sink.is_paused() == false {
-                let curr = app.show_song_curr_time()?;
-                let dur = app.show_song_duration()?;
-                app.show_song_progress(curr, dur);
-                if app.audio_sink.empty() && app.curr_songid != 0 {
-                    match app.play_state {
-                        PlayState::Pause(true) => {
-                            app.audio_sink.append(Decoder::new(
-                                BufReader::new(File::open(app.audio_path.clone())?)
-                            )?);
-                            app.audio_sink.pause();
-                            app.play_state = PlayState::Pause(false)
-                        },
-                        PlayState::Play(false) => {
-                            app.curr_songid += 1;
-                            if app.curr_songid > app.curr_songnum {
-                                app.curr_songid = 1;
+                // 输入跳转编号或时间戳时，Backspace、Esc、Enter用于编辑和确认
+                if key.kind == KeyEventKind::Press {
+                    match key.code {
+                        KeyCode::Char(c @ ('0'..='9' | ':')) if key.modifiers.is_empty() || key.modifiers == KeyModifiers::SHIFT => {
+                            if app.jump_input.len() < 12 {
+                                app.jump_input.push(c);
                             }
-                            app.audio_path = app.curr_playlist[(app.curr_songid - 1) as usize].clone();
-                            app.audio_sink.append(Decoder::new(
-                                BufReader::new(File::open(app.audio_path.clone())?)
-                            )?);
-                        }
+                            continue;
+                        },
+                        KeyCode::Backspace if !app.jump_input.is_empty() => {
+                            app.jump_input.pop();
+                            continue;
+                        },
+                        KeyCode::Esc if !app.jump_input.is_empty() => {
+                            app.jump_input.clear();
+                            continue;
+                        },
+                        KeyCode::Enter if !app.jump_input.is_empty() => {
+                            app.confirm_jump();
+                            continue;
+                        },
                         _ => {}
                     }
-                    app.show_song_info();
                 }
-            }
-            else {
-                thread::sleep(Duration::from_millis(300));
+                let action = match app.keymap.action(&key) {
+                    Some(action) => action,
+                    None => continue,
+                };
+                // 可重复的操作在按住时持续触发，其余操作只响应按下
+                if key.kind == KeyEventKind::Release || (key.kind == KeyEventKind::Repeat && !action.repeatable()) {
+                    continue;
+                }
+                match action {
+                    Action::Quit => {
+                        let _ = SavedState {volume: app.volume}.save();
+                        app.player.send(Command::Quit);
+                        return Ok(());
+                    },
+                    // 操作失败（如文件夹无法读取）只提示，不退出
+                    action => if let Err(err) = app.run_action(action) {
+                        app.notify(err.to_string());
+                    },
+                }
             }
         }
+        // 播放线程独立运行，这里只需要接收它的状态，按住按键时也不会耽误切歌
+        app.process_events()?;
     }
 }
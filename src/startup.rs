@@ -0,0 +1,119 @@
+use std::{
+    error::Error,
+    fs,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use raplay_core::{
+    engine::{PlayMode, Player, PlayerOptions},
+    fade::Crossfade,
+    library::{collect_audio_files, is_audio_file, Library},
+    output::Output,
+    playlist::{is_playlist_file, load_playlist, PlaylistEntry},
+};
+
+use crate::{cli::Args, config::Config, state::SavedState};
+
+// 界面和无界面模式共用的启动步骤：启动播放线程，把命令行中的路径展开为歌曲
+
+// 初始音量（0.0 ~ 1.0）：命令行指定的优先，否则为上次退出时保存的音量
+pub fn start_volume(args: &Args) -> f32 {
+    args.volume.map(|v| v as f32 / 100.0).unwrap_or_else(|| SavedState::load().volume).clamp(0.0, 1.0)
+}
+
+// 按命令行参数和配置启动播放线程，返回播放线程和需要提示的问题
+pub fn spawn_player(args: &Args, config: &Config, mode: PlayMode, volume: f32) -> Result<(Player, Option<String>), Box<dyn Error>> {
+    // 命令行的--crossfade优先于配置文件，为0时关闭
+    let crossfade = args.crossfade.or(config.crossfade)
+        .filter(|&secs| secs.is_finite() && secs > 0.0)
+        .map(|secs| Crossfade {duration: Duration::from_secs_f32(secs.min(30.0)), curve: config.crossfade_curve});
+    let options = |output| PlayerOptions {mode, volume, seed: args.seed, output, crossfade};
+    let player = match &args.output {
+        Some(Output::Null {..}) => Player::spawn(options(Output::Null {realtime: !args.fast}))?,
+        Some(Output::Wav {path, ..}) => Player::spawn(options(Output::Wav {path: path.clone(), realtime: !args.fast}))?,
+        Some(Output::Device) => Player::spawn(options(Output::Device))?,
+        // 没有指定输出时，打不开音频设备（如在容器、服务器上）就照常播放但丢弃声音
+        None => match Player::spawn(options(Output::Device)) {
+            Ok(player) => player,
+            Err(err) => {
+                let player = Player::spawn(options(Output::Null {realtime: true}))?;
+                return Ok((player, Some(format!("No audio device ({}), playing without sound", err))));
+            },
+        },
+    };
+    Ok((player, None))
+}
+
+// 文件夹内（含子文件夹）的所有歌曲，以及路径不是有效的UTF-8而被跳过的文件数；在音乐库中时从库索引读取
+pub fn folder_tracks(library: &Library, dir: &Path) -> Result<(Vec<String>, usize), Box<dyn Error>> {
+    let mut list = vec![];
+    let mut skipped = 0;
+    if library.contains(dir) {
+        list = library.tracks_under(dir);
+    }
+    else {
+        skipped = collect_audio_files(dir, &mut list)?;
+    }
+    Ok((list, skipped))
+}
+
+// 播放列表文件中能找到的歌曲，以及找不到而被跳过的文件数
+pub fn playlist_tracks(path: &Path) -> Result<(Vec<PlaylistEntry>, usize), Box<dyn Error>> {
+    let entries = load_playlist(path)?;
+    let total = entries.len();
+    let found = entries.into_iter()
+        .filter(|entry| Path::new(&entry.path).is_file())
+        .collect::<Vec<_>>();
+    let missing = total - found.len();
+    Ok((found, missing))
+}
+
+// 命令行中的路径展开后的结果
+#[derive(Default)]
+pub struct ArgTracks {
+    pub tracks: Vec<String>,
+    pub hints: Vec<PlaylistEntry>,      // 播放列表文件中记录的标题和时长
+    pub start_dir: Option<PathBuf>,     // 第一个文件夹，或第一个文件所在的文件夹
+    pub failed: Vec<String>,            // 无法打开的参数
+    pub missing: usize,                 // 播放列表中找不到的文件数
+    pub skipped: usize,                 // 文件夹中路径不是有效的UTF-8而被跳过的文件数
+}
+
+// 把命令行中的音频文件、文件夹（递归）和播放列表展开为歌曲路径
+pub fn expand_args(paths: &[PathBuf], library: &Library) -> ArgTracks {
+    let mut out = ArgTracks::default();
+    for arg in paths {
+        let path = fs::canonicalize(arg).unwrap_or_else(|_| arg.clone());
+        let name = path.to_string_lossy().into_owned();
+        let tracks = if path.is_dir() {
+            out.start_dir.get_or_insert_with(|| path.clone());
+            folder_tracks(library, &path).map(|(tracks, skipped)| {
+                out.skipped += skipped;
+                tracks
+            })
+        }
+        else if path.is_file() && is_playlist_file(&name) {
+            playlist_tracks(&path).map(|(entries, missing)| {
+                out.missing += missing;
+                let tracks = entries.iter().map(|entry| entry.path.clone()).collect();
+                out.hints.extend(entries);
+                tracks
+            })
+        }
+        else if path.is_file() && is_audio_file(&name) {
+            Ok(vec![name])
+        }
+        else {
+            Err("not an audio file, folder or playlist".into())
+        };
+        match tracks {
+            Ok(tracks) => out.tracks.extend(tracks),
+            Err(_) => out.failed.push(arg.to_string_lossy().into_owned()),
+        }
+        if out.start_dir.is_none() && path.is_file() {
+            out.start_dir = path.parent().map(Path::to_path_buf);
+        }
+    }
+    out
+}
@@ -0,0 +1,42 @@
+use std::{
+    error::Error,
+    fs::{self, File},
+    io::{BufReader, BufWriter},
+    path::PathBuf,
+};
+
+use serde::{Deserialize, Serialize};
+
+// 需要在重启之间保留的播放器状态，保存在用户数据目录下的raplay/state.json
+#[derive(Serialize, Deserialize)]
+#[serde(default)]
+pub struct SavedState {
+    pub volume: f32,    // 音量（0.0 ~ 1.0），静音时保存的是静音前的音量
+}
+
+impl Default for SavedState {
+    fn default() -> Self {
+        Self {volume: 1.0}
+    }
+}
+
+impl SavedState {
+    pub fn path() -> Option<PathBuf> {
+        dirs::data_dir().map(|dir| dir.join("raplay").join("state.json"))
+    }
+    // 读取保存的状态，文件不存在或损坏时返回默认值
+    pub fn load() -> Self {
+        Self::path()
+            .and_then(|path| File::open(path).ok())
+            .and_then(|file| serde_json::from_reader(BufReader::new(file)).ok())
+            .unwrap_or_default()
+    }
+    pub fn save(&self) -> Result<(), Box<dyn Error>> {
+        let path = Self::path().ok_or("no data directory")?;
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        serde_json::to_writer(BufWriter::new(File::create(path)?), self)?;
+        Ok(())
+    }
+}
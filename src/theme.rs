@@ -0,0 +1,182 @@
+use std::{collections::BTreeMap, env, str::FromStr};
+
+use ratatui::{
+    style::{Color, Modifier, Style},
+    widgets::{Block, BorderType},
+};
+use serde::Deserialize;
+
+// 界面配色和字符，颜色为Color::Reset时使用终端自身的颜色
+#[derive(Clone)]
+pub struct Theme {
+    pub name: String,
+    pub fg: Color,              // 文字
+    pub bg: Color,              // 背景
+    pub accent: Color,          // 歌名、标题、焦点所在的边框、进度条已播放部分
+    pub highlight: Color,       // 列表中选中条目的背景，为Reset时改用反色显示
+    pub dim: Color,             // 进度条未播放部分等次要内容
+    pub border: BorderType,
+    pub progress_glyphs: Vec<char>,  // 进度条字符：已播放、[当前位置]、未播放；为空时用部分方块字符平滑绘制
+    pub volume_glyphs: [char; 2],    // 音量条字符：有、无
+}
+
+// 配置文件中的[themes.名称]，省略的项沿用base指定的主题（默认为default）
+//
+// [themes.mine]
+// base = "nord"
+// accent = "#ffb86c"          # 颜色名、#rrggbb或0~255的色号
+// border = "rounded"          # plain、rounded、double、thick
+// progress = "=>-"            # 2或3个字符，"smooth"为平滑方块
+#[derive(Deserialize, Default)]
+#[serde(default)]
+pub struct ThemeSpec {
+    pub base: Option<String>,
+    pub fg: Option<String>,
+    pub bg: Option<String>,
+    pub accent: Option<String>,
+    pub highlight: Option<String>,
+    pub dim: Option<String>,
+    pub border: Option<String>,
+    pub progress: Option<String>,
+    pub volume: Option<String>,
+}
+
+pub const BUILTIN: [&str; 5] = ["default", "mono", "nord", "gruvbox", "ascii"];
+
+impl Theme {
+    pub fn builtin(name: &str) -> Option<Self> {
+        let default = Self {
+            name: name.to_string(),
+            fg: Color::Reset,
+            bg: Color::Reset,
+            accent: Color::Reset,
+            highlight: Color::Reset,
+            dim: Color::DarkGray,
+            border: BorderType::Plain,
+            progress_glyphs: vec![],
+            volume_glyphs: ['▮', '▯'],
+        };
+        match name {
+            "default" => Some(default),
+            // 不使用任何颜色，只靠反色、加粗和下划线区分
+            "mono" => Some(Self {dim: Color::Reset, progress_glyphs: vec!['█', '░'], ..default}),
+            "nord" => Some(Self {
+                fg: Color::Rgb(0xd8, 0xde, 0xe9),
+                bg: Color::Rgb(0x2e, 0x34, 0x40),
+                accent: Color::Rgb(0x88, 0xc0, 0xd0),
+                highlight: Color::Rgb(0x43, 0x4c, 0x5e),
+                dim: Color::Rgb(0x4c, 0x56, 0x6a),
+                border: BorderType::Rounded,
+                ..default
+            }),
+            "gruvbox" => Some(Self {
+                fg: Color::Rgb(0xeb, 0xdb, 0xb2),
+                bg: Color::Rgb(0x28, 0x28, 0x28),
+                accent: Color::Rgb(0xfa, 0xbd, 0x2f),
+                highlight: Color::Rgb(0x50, 0x49, 0x45),
+                dim: Color::Rgb(0x66, 0x5c, 0x54),
+                border: BorderType::Thick,
+                progress_glyphs: vec!['━', '╸', '─'],
+                ..default
+            }),
+            // 只用ASCII字符，适合字体不全的终端
+            "ascii" => Some(Self {
+                accent: Color::Cyan,
+                progress_glyphs: vec!['=', '>', '-'],
+                volume_glyphs: ['#', '.'],
+                ..default
+            }),
+            _ => None,
+        }
+    }
+    // 按配置生成主题，base可以是内置主题或之前已定义的主题
+    fn from_spec(name: &str, spec: &ThemeSpec, themes: &[Theme]) -> Result<Self, String> {
+        let base = spec.base.as_deref().unwrap_or("default");
+        let mut theme = themes.iter()
+            .find(|theme| theme.name == base)
+            .cloned()
+            .ok_or_else(|| format!("unknown base theme \"{}\"", base))?;
+        theme.name = name.to_string();
+        let color = |value: &Option<String>, old: Color| match value {
+            Some(value) => Color::from_str(value).map_err(|_| format!("invalid colour \"{}\"", value)),
+            None => Ok(old),
+        };
+        theme.fg = color(&spec.fg, theme.fg)?;
+        theme.bg = color(&spec.bg, theme.bg)?;
+        theme.accent = color(&spec.accent, theme.accent)?;
+        theme.highlight = color(&spec.highlight, theme.highlight)?;
+        theme.dim = color(&spec.dim, theme.dim)?;
+        if let Some(border) = &spec.border {
+            theme.border = match border.to_ascii_lowercase().as_str() {
+                "plain" => BorderType::Plain,
+                "rounded" => BorderType::Rounded,
+                "double" => BorderType::Double,
+                "thick" => BorderType::Thick,
+                _ => return Err(format!("invalid border \"{}\"", border)),
+            };
+        }
+        if let Some(progress) = &spec.progress {
+            theme.progress_glyphs = match progress.chars().count() {
+                _ if progress == "smooth" => vec![],
+                2 | 3 => progress.chars().collect(),
+                _ => return Err(format!("progress needs 2 or 3 characters, got \"{}\"", progress)),
+            };
+        }
+        if let Some(volume) = &spec.volume {
+            let chars = volume.chars().collect::<Vec<_>>();
+            theme.volume_glyphs = match chars[..] {
+                [on, off] => [on, off],
+                _ => return Err(format!("volume needs 2 characters, got \"{}\"", volume)),
+            };
+        }
+        Ok(theme)
+    }
+    // 内置主题加上配置文件中的主题；出错的主题被跳过，返回错误信息
+    pub fn load_all(specs: &BTreeMap<String, ThemeSpec>) -> (Vec<Self>, Vec<String>) {
+        let mut themes = BUILTIN.iter().filter_map(|name| Self::builtin(name)).collect::<Vec<_>>();
+        let mut errors = vec![];
+        for (name, spec) in specs {
+            match Self::from_spec(name, spec, &themes) {
+                Ok(theme) => {
+                    themes.retain(|old| old.name != theme.name);
+                    themes.push(theme);
+                },
+                Err(err) => errors.push(format!("theme {}: {}", name, err)),
+            }
+        }
+        (themes, errors)
+    }
+    // 设置了NO_COLOR或终端为dumb时视为不支持颜色
+    pub fn color_supported() -> bool {
+        !matches!(env::var_os("NO_COLOR"), Some(value) if !value.is_empty())
+            && !matches!(env::var("TERM").as_deref(), Ok("dumb"))
+    }
+
+    pub fn base(&self) -> Style {
+        Style::new().fg(self.fg).bg(self.bg)
+    }
+    pub fn accent(&self) -> Style {
+        Style::new().fg(self.accent).add_modifier(Modifier::BOLD)
+    }
+    pub fn dim(&self) -> Style {
+        Style::new().fg(self.dim)
+    }
+    // 带边框的面板，focused时边框和标题使用强调色
+    pub fn block(&self, focused: bool) -> Block<'static> {
+        let block = Block::bordered().border_type(self.border).style(self.base());
+        if focused {
+            block.border_style(Style::new().fg(self.accent)).title_style(self.accent())
+        }
+        else {
+            block
+        }
+    }
+    // 列表选中条目：焦点所在的列表高亮显示，另一个列表加下划线
+    pub fn selection(&self, focused: bool) -> Style {
+        match (focused, self.highlight) {
+            (false, _) => Style::new().add_modifier(Modifier::UNDERLINED),
+            (true, Color::Reset) => Style::new().add_modifier(Modifier::REVERSED),
+            (true, highlight) => Style::new().bg(highlight).add_modifier(Modifier::BOLD),
+        }
+    }
+}